@@ -38,6 +38,10 @@ impl<T, O, E> Action for AnyAction<T, O, E> {
     fn redo(&mut self, target: &mut Self::Target) -> crate::Result<Self> {
         self.action.redo(target)
     }
+
+    fn size_hint(&self) -> usize {
+        self.action.size_hint()
+    }
 }
 
 impl<T, O, E> Debug for AnyAction<T, O, E> {
@@ -0,0 +1,518 @@
+//! Zero-copy archives of the data structures.
+//!
+//! A record or history can be archived into bytes that can be inspected directly,
+//! without deserializing the actions. This makes it cheap to, for example,
+//! show the entries and branches of a large history when it is loaded.
+//! The display text of every entry is stored in the archive for this purpose.
+//! The archive is only deserialized when it is needed, for example when the user
+//! starts navigating the history.
+//!
+//! The same parts of the data structures are archived as are stored by the `persist` module,
+//! and they are checked the same way when the archive is deserialized.
+//!
+//! Requires the `rkyv` feature to be enabled.
+//!
+//! # Examples
+//! ```
+//! # use undo::{archive, History};
+//! # use core::fmt::{self, Display, Formatter};
+//! # #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+//! # struct Add(char);
+//! # impl undo::Action for Add {
+//! #     type Target = String;
+//! #     type Output = ();
+//! #     type Error = &'static str;
+//! #     fn apply(&mut self, s: &mut String) -> undo::Result<Add> {
+//! #         s.push(self.0);
+//! #         Ok(())
+//! #     }
+//! #     fn undo(&mut self, s: &mut String) -> undo::Result<Add> {
+//! #         self.0 = s.pop().ok_or("s is empty")?;
+//! #         Ok(())
+//! #     }
+//! # }
+//! # impl Display for Add {
+//! #     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+//! #         write!(f, "Add '{}'", self.0)
+//! #     }
+//! # }
+//! # fn main() {
+//! let mut target = String::new();
+//! let mut history = History::new();
+//! history.apply(&mut target, Add('a')).unwrap();
+//! history.apply(&mut target, Add('b')).unwrap();
+//!
+//! let bytes = archive::archive_history(&history).unwrap();
+//! let archived = archive::access_history::<Add>(&bytes).unwrap();
+//! assert_eq!(archived.len(), 2);
+//! assert_eq!(archived.entries().last().unwrap().to_string(), "Add 'b'");
+//!
+//! let history: History<Add> = archived.deserialize().unwrap();
+//! assert_eq!(history.current(), 2);
+//! # }
+//! ```
+
+use crate::convert::{self, BranchData, EntryData, HistoryData, Invalid, RecordData};
+use crate::{History, Record};
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+use rkyv::api::high::{HighDeserializer, HighSerializer, HighValidator};
+use rkyv::bytecheck::CheckBytes;
+use rkyv::rancor::{Error, Fallible, Source};
+use rkyv::ser::allocator::ArenaHandle;
+use rkyv::util::AlignedVec;
+use rkyv::{Archive, Deserialize, Place, Serialize};
+
+/// A record as it is archived.
+#[derive(Archive, Serialize, Deserialize)]
+#[rkyv(archived = ArchivedRecord)]
+pub struct RecordArchive<A> {
+    entries: Vec<EntryArchive<A>>,
+    current: u64,
+    limit: u64,
+    saved: Option<u64>,
+}
+
+/// A history as it is archived.
+#[derive(Archive, Serialize, Deserialize)]
+#[rkyv(archived = ArchivedHistory)]
+pub struct HistoryArchive<A> {
+    record: RecordArchive<A>,
+    branch: u64,
+    next: u64,
+    saved: Option<[u64; 2]>,
+    branches: Vec<BranchArchive<A>>,
+}
+
+/// A branch as it is archived.
+#[derive(Archive, Serialize, Deserialize)]
+#[rkyv(archived = ArchivedBranch)]
+pub struct BranchArchive<A> {
+    id: u64,
+    parent: [u64; 2],
+    entries: Vec<EntryArchive<A>>,
+}
+
+/// An entry as it is archived.
+#[derive(Archive, Serialize, Deserialize)]
+#[rkyv(archived = ArchivedEntry)]
+pub struct EntryArchive<A> {
+    action: A,
+    group: Vec<A>,
+    label: Option<String>,
+    text: String,
+    timestamp: Option<i64>,
+    modified: Option<i64>,
+}
+
+/// Archives a borrowed action as if it was owned, so it does not have to be cloned.
+struct ByRef<'a, A>(&'a A);
+
+impl<A: Archive> Archive for ByRef<'_, A> {
+    type Archived = A::Archived;
+    type Resolver = A::Resolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        self.0.resolve(resolver, out);
+    }
+}
+
+impl<A: Serialize<S>, S: Fallible + ?Sized> Serialize<S> for ByRef<'_, A> {
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+/// Archives the record.
+///
+/// # Errors
+/// Returns an error if an action can not be archived.
+pub fn archive_record<A, F>(record: &Record<A, F>) -> Result<AlignedVec, Error>
+where
+    A: fmt::Display
+        + Archive
+        + for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, Error>>,
+{
+    rkyv::to_bytes(&record_ref(convert::record_data(record)))
+}
+
+/// Archives the history.
+///
+/// # Errors
+/// Returns an error if an action can not be archived.
+pub fn archive_history<A, F>(history: &History<A, F>) -> Result<AlignedVec, Error>
+where
+    A: fmt::Display
+        + Archive
+        + for<'a> Serialize<HighSerializer<AlignedVec, ArenaHandle<'a>, Error>>,
+{
+    rkyv::to_bytes(&history_ref(convert::history_data(history)))
+}
+
+/// Accesses an archived record without deserializing it.
+///
+/// The bytes are validated before they are accessed.
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid archived record.
+pub fn access_record<A>(bytes: &[u8]) -> Result<&ArchivedRecord<A>, Error>
+where
+    A: Archive,
+    A::Archived: for<'a> CheckBytes<HighValidator<'a, Error>>,
+{
+    rkyv::access(bytes)
+}
+
+/// Accesses an archived history without deserializing it.
+///
+/// The bytes are validated before they are accessed.
+///
+/// # Errors
+/// Returns an error if the bytes are not a valid archived history.
+pub fn access_history<A>(bytes: &[u8]) -> Result<&ArchivedHistory<A>, Error>
+where
+    A: Archive,
+    A::Archived: for<'a> CheckBytes<HighValidator<'a, Error>>,
+{
+    rkyv::access(bytes)
+}
+
+impl<A: Archive> ArchivedRecord<A> {
+    /// Returns the number of actions in the record.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the record is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the position of the current action.
+    pub fn current(&self) -> usize {
+        self.current.to_native() as usize
+    }
+
+    /// Returns the limit of the record.
+    pub fn limit(&self) -> usize {
+        self.limit.to_native() as usize
+    }
+
+    /// Returns `true` if the target was in a saved state when it was archived.
+    pub fn is_saved(&self) -> bool {
+        self.saved() == Some(self.current())
+    }
+
+    /// Returns the position where the target was saved, if any.
+    pub fn saved(&self) -> Option<usize> {
+        self.saved.as_ref().map(|saved| saved.to_native() as usize)
+    }
+
+    /// Returns the entries, from the oldest to the newest.
+    pub fn entries(&self) -> &[ArchivedEntry<A>] {
+        &self.entries
+    }
+
+    /// Deserializes the record.
+    ///
+    /// # Errors
+    /// Returns an error if an action can not be deserialized, or if the record is invalid.
+    pub fn deserialize<F>(&self) -> Result<Record<A, F>, Error>
+    where
+        A::Archived: Deserialize<A, HighDeserializer<Error>>,
+    {
+        let data = record_data(rkyv::deserialize(self)?).map_err(Error::new)?;
+        convert::into_record(data).map_err(Error::new)
+    }
+}
+
+impl<A: Archive> ArchivedHistory<A> {
+    /// Returns the number of actions in the current branch of the history.
+    pub fn len(&self) -> usize {
+        self.record.len()
+    }
+
+    /// Returns `true` if the current branch of the history is empty.
+    pub fn is_empty(&self) -> bool {
+        self.record.is_empty()
+    }
+
+    /// Returns the position of the current action.
+    pub fn current(&self) -> usize {
+        self.record.current()
+    }
+
+    /// Returns the id of the current branch.
+    pub fn branch(&self) -> usize {
+        self.branch.to_native() as usize
+    }
+
+    /// Returns `true` if the target was in a saved state when it was archived.
+    pub fn is_saved(&self) -> bool {
+        self.record.is_saved()
+    }
+
+    /// Returns the branch and position where the target was saved,
+    /// if it is not in the current branch.
+    pub fn saved(&self) -> Option<(usize, usize)> {
+        self.saved
+            .as_ref()
+            .map(|[branch, current]| (branch.to_native() as usize, current.to_native() as usize))
+    }
+
+    /// Returns the entries in the current branch, from the oldest to the newest.
+    pub fn entries(&self) -> &[ArchivedEntry<A>] {
+        self.record.entries()
+    }
+
+    /// Returns the current branch as a record.
+    pub fn record(&self) -> &ArchivedRecord<A> {
+        &self.record
+    }
+
+    /// Returns the branches that are not the current branch.
+    pub fn branches(&self) -> &[ArchivedBranch<A>] {
+        &self.branches
+    }
+
+    /// Deserializes the history.
+    ///
+    /// # Errors
+    /// Returns an error if an action can not be deserialized, or if the history is invalid.
+    pub fn deserialize<F>(&self) -> Result<History<A, F>, Error>
+    where
+        A::Archived: Deserialize<A, HighDeserializer<Error>>,
+    {
+        let data = history_data(rkyv::deserialize(self)?).map_err(Error::new)?;
+        convert::into_history(data).map_err(Error::new)
+    }
+}
+
+impl<A: Archive> ArchivedBranch<A> {
+    /// Returns the id of the branch.
+    pub fn id(&self) -> usize {
+        self.id.to_native() as usize
+    }
+
+    /// Returns the branch and position the branch is attached to.
+    pub fn parent(&self) -> (usize, usize) {
+        let [branch, current] = &self.parent;
+        (branch.to_native() as usize, current.to_native() as usize)
+    }
+
+    /// Returns the entries of the branch after the parent position.
+    pub fn entries(&self) -> &[ArchivedEntry<A>] {
+        &self.entries
+    }
+}
+
+impl<A: Archive> ArchivedEntry<A> {
+    /// Returns the archived action.
+    pub fn action(&self) -> &A::Archived {
+        &self.action
+    }
+
+    /// Returns the label of the entry, if it was created from a group with a label.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_ref().map(|label| label.as_str())
+    }
+}
+
+/// Writes the text of the entry as it was displayed when it was archived.
+impl<A: Archive> fmt::Display for ArchivedEntry<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+fn size(n: u64) -> Result<usize, Invalid> {
+    usize::try_from(n).map_err(|_| Invalid::Overflow)
+}
+
+fn record_ref<A: fmt::Display>(data: RecordData<&A>) -> RecordArchive<ByRef<'_, A>> {
+    RecordArchive {
+        entries: data.entries.into_iter().map(entry_ref).collect(),
+        current: data.current as u64,
+        limit: data.limit as u64,
+        saved: data.saved.map(|saved| saved as u64),
+    }
+}
+
+fn history_ref<A: fmt::Display>(data: HistoryData<&A>) -> HistoryArchive<ByRef<'_, A>> {
+    HistoryArchive {
+        record: record_ref(data.record),
+        branch: data.branch as u64,
+        next: data.next as u64,
+        saved: data
+            .saved
+            .map(|(branch, current)| [branch as u64, current as u64]),
+        branches: data
+            .branches
+            .into_iter()
+            .map(|branch| BranchArchive {
+                id: branch.id as u64,
+                parent: [branch.parent.0 as u64, branch.parent.1 as u64],
+                entries: branch.entries.into_iter().map(entry_ref).collect(),
+            })
+            .collect(),
+    }
+}
+
+fn entry_ref<A: fmt::Display>(data: EntryData<&A>) -> EntryArchive<ByRef<'_, A>> {
+    EntryArchive {
+        // The same text as the `Display` of the entry.
+        text: data
+            .label
+            .clone()
+            .unwrap_or_else(|| data.action.to_string()),
+        action: ByRef(data.action),
+        group: data.group.into_iter().map(ByRef).collect(),
+        label: data.label,
+        timestamp: data.timestamp,
+        modified: data.modified,
+    }
+}
+
+fn record_data<A>(archive: RecordArchive<A>) -> Result<RecordData<A>, Invalid> {
+    Ok(RecordData {
+        entries: archive.entries.into_iter().map(entry_data).collect(),
+        current: size(archive.current)?,
+        limit: size(archive.limit)?,
+        saved: archive.saved.map(size).transpose()?,
+    })
+}
+
+fn history_data<A>(archive: HistoryArchive<A>) -> Result<HistoryData<A>, Invalid> {
+    let branches = archive.branches.into_iter().map(|branch| {
+        let [parent, current] = branch.parent;
+        Ok(BranchData {
+            id: size(branch.id)?,
+            parent: (size(parent)?, size(current)?),
+            entries: branch.entries.into_iter().map(entry_data).collect(),
+        })
+    });
+    Ok(HistoryData {
+        record: record_data(archive.record)?,
+        branch: size(archive.branch)?,
+        next: size(archive.next)?,
+        saved: archive
+            .saved
+            .map(|[branch, current]| Ok((size(branch)?, size(current)?)))
+            .transpose()?,
+        branches: branches.collect::<Result<_, _>>()?,
+    })
+}
+
+fn entry_data<A>(archive: EntryArchive<A>) -> EntryData<A> {
+    EntryData {
+        action: archive.action,
+        group: archive.group,
+        label: archive.label,
+        timestamp: archive.timestamp,
+        modified: archive.modified,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use alloc::{string::String, string::ToString, vec::Vec};
+    use core::fmt::{self, Display, Formatter};
+
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+    struct Add(char);
+
+    impl Action for Add {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Add> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Add> {
+            self.0 = s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+    }
+
+    impl Display for Add {
+        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+            write!(f, "Add '{}'", self.0)
+        }
+    }
+
+    #[test]
+    fn record() {
+        let mut target = String::new();
+        let mut record = record::Builder::default().limit(10).build();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.begin_group(Some(String::from("bc")));
+        record.apply(&mut target, Add('b')).unwrap();
+        record.apply(&mut target, Add('c')).unwrap();
+        record.end_group();
+        record.set_saved(true);
+        record.undo(&mut target).unwrap().unwrap();
+
+        let bytes = archive::archive_record(&record).unwrap();
+        let archived = archive::access_record::<Add>(&bytes).unwrap();
+        assert_eq!(archived.len(), 2);
+        assert_eq!(archived.current(), 1);
+        assert_eq!(archived.limit(), 10);
+        assert_eq!(archived.saved(), Some(2));
+        let texts: Vec<_> = archived.entries().iter().map(|e| e.to_string()).collect();
+        assert_eq!(texts, ["Add 'a'", "bc"]);
+        assert_eq!(archived.entries()[0].action().0, 'a');
+
+        let mut record: Record<Add> = archived.deserialize().unwrap();
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abc");
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn history() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Add('a')).unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        let ab = history.branch();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Add('c')).unwrap();
+        let ac = history.branch();
+
+        let bytes = archive::archive_history(&history).unwrap();
+        let archived = archive::access_history::<Add>(&bytes).unwrap();
+        assert_eq!(archived.branch(), ac);
+        assert_eq!(archived.branches().len(), 1);
+        let branch = &archived.branches()[0];
+        assert_eq!(branch.id(), ab);
+        assert_eq!(branch.parent(), (ac, 1));
+        assert_eq!(branch.entries()[0].to_string(), "Add 'b'");
+
+        let mut history: History<Add> = archived.deserialize().unwrap();
+        history.go_to(&mut target, ab, 2).unwrap().unwrap();
+        assert_eq!(target, "ab");
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(archive::access_record::<Add>(&[1, 2, 3]).is_err());
+
+        let mut target = String::new();
+        let mut record = record::Builder::default().limit(2).build();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.apply(&mut target, Add('b')).unwrap();
+        let mut archive = super::record_ref(convert::record_data(&record));
+        archive.limit = 1;
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&archive).unwrap();
+        let archived = archive::access_record::<Add>(&bytes).unwrap();
+        let error = archived.deserialize::<fn(Signal)>().err().unwrap();
+        assert!(error.to_string().contains("more entries than the limit"));
+    }
+}
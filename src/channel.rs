@@ -0,0 +1,116 @@
+//! Delivery of signals through channels.
+//!
+//! The slots returned by the functions in this module can be used as the `F` parameter of
+//! the data structures, either directly or boxed into the default `Box<dyn FnMut(Signal)>`.
+//! This is useful when the signals can not be handled inside the slot,
+//! for example when they are handled by an async event loop.
+
+use crate::Signal;
+
+/// Returns a slot that sends the signals through a channel, and the receiving half of the channel.
+///
+/// Signals sent after the receiver has been dropped are ignored.
+///
+/// Requires the `std` feature to be enabled.
+///
+/// # Examples
+/// ```
+/// # use undo::{record::Builder, Signal};
+/// # include!("../add.rs");
+/// # fn main() {
+/// let (slot, receiver) = undo::channel::channel();
+/// let mut target = String::new();
+/// let mut record = Builder::new().connect(slot).build();
+/// record.apply(&mut target, Add('a')).unwrap();
+/// assert_eq!(receiver.try_iter().find(Signal::is_coarse), Some(Signal::Undo(true)));
+/// # }
+/// ```
+pub fn channel() -> (impl FnMut(Signal) + Send, std::sync::mpsc::Receiver<Signal>) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let slot = move |signal| {
+        let _ = sender.send(signal);
+    };
+    (slot, receiver)
+}
+
+/// Returns a slot that sends the signals through a channel, and the receiving half of the channel.
+///
+/// The receiver implements [`Stream`], so the signals can be awaited by an async event loop.
+/// Signals sent after the receiver has been dropped are ignored.
+///
+/// Requires the `futures` feature to be enabled.
+///
+/// [`Stream`]: https://docs.rs/futures/latest/futures/stream/trait.Stream.html
+#[cfg(feature = "futures")]
+pub fn stream() -> (
+    impl FnMut(Signal) + Send,
+    futures_channel::mpsc::UnboundedReceiver<Signal>,
+) {
+    let (sender, receiver) = futures_channel::mpsc::unbounded();
+    let slot = move |signal| {
+        let _ = sender.unbounded_send(signal);
+    };
+    (slot, receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use alloc::{string::String, vec::Vec};
+
+    struct Add(char);
+
+    impl Action for Add {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Add> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Add> {
+            self.0 = s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn channel() {
+        let (slot, receiver) = channel::channel();
+        let mut target = String::new();
+        let mut record = record::Builder::new().connect(slot).build();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.undo(&mut target).unwrap().unwrap();
+        let coarse: Vec<_> = receiver.try_iter().filter(Signal::is_coarse).collect();
+        assert_eq!(
+            coarse,
+            [
+                Signal::Undo(true),
+                Signal::Saved(false),
+                Signal::Redo(true),
+                Signal::Undo(false),
+                Signal::Saved(true),
+            ]
+        );
+        drop(receiver);
+        record.redo(&mut target).unwrap().unwrap();
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn stream() {
+        let (slot, mut receiver) = channel::stream();
+        let mut target = String::new();
+        let mut history = history::Builder::new().connect(slot).build();
+        history.apply(&mut target, Add('a')).unwrap();
+        let mut coarse = Vec::new();
+        while let Ok(signal) = receiver.try_recv() {
+            if signal.is_coarse() {
+                coarse.push(signal);
+            }
+        }
+        assert_eq!(coarse, [Signal::Undo(true), Signal::Saved(false)]);
+    }
+}
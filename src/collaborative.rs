@@ -0,0 +1,430 @@
+//! A record of actions made by multiple authors.
+
+use crate::{Action, Merged, Record, Result, Signal, Transform};
+use alloc::boxed::Box;
+use core::fmt;
+
+/// A record of actions made by multiple authors on a shared target.
+///
+/// Every action is tagged with the author that applied it, and each author can only
+/// undo and redo their own actions. When an author undoes an action that other authors
+/// have applied actions after, the action is [transformed](../trait.Transform.html)
+/// past those actions before it is undone, so the changes made by the other authors are kept.
+///
+/// Like the [record](../record/struct.Record.html), applying a new action removes
+/// all the actions that can be redone. Actions can not be grouped, since only single
+/// actions are transformed when they are reordered.
+///
+/// # Examples
+/// ```
+/// # use undo::{Action, CollaborativeRecord, Transform};
+/// struct Insert(usize, char);
+///
+/// impl Action for Insert {
+///     type Target = String;
+///     type Output = ();
+///     type Error = &'static str;
+///
+///     fn apply(&mut self, s: &mut String) -> undo::Result<Insert> {
+///         s.insert(self.0, self.1);
+///         Ok(())
+///     }
+///
+///     fn undo(&mut self, s: &mut String) -> undo::Result<Insert> {
+///         s.remove(self.0);
+///         Ok(())
+///     }
+/// }
+///
+/// impl Transform for Insert {
+///     fn include(&mut self, other: &Insert) {
+///         if other.0 < self.0 || (other.0 == self.0 && other.1 < self.1) {
+///             self.0 += 1;
+///         }
+///     }
+///
+///     fn exclude(&mut self, other: &Insert) {
+///         if other.0 < self.0 {
+///             self.0 -= 1;
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut target = String::new();
+/// let mut record = CollaborativeRecord::new();
+/// record.apply(&mut target, "alice", Insert(0, 'a')).unwrap();
+/// record.apply(&mut target, "bob", Insert(1, 'b')).unwrap();
+/// assert_eq!(target, "ab");
+/// record.undo(&mut target, &"alice").unwrap().unwrap();
+/// assert_eq!(target, "b");
+/// record.redo(&mut target, &"alice").unwrap().unwrap();
+/// assert_eq!(target, "ab");
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct CollaborativeRecord<A, I, F = Box<dyn FnMut(Signal)>> {
+    record: Record<Authored<A, I>, F>,
+}
+
+impl<A, I> CollaborativeRecord<A, I> {
+    /// Returns a new collaborative record.
+    pub fn new() -> CollaborativeRecord<A, I> {
+        CollaborativeRecord {
+            record: Record::new(),
+        }
+    }
+}
+
+impl<A, I, F> CollaborativeRecord<A, I, F> {
+    /// Returns the number of actions in the record.
+    pub fn len(&self) -> usize {
+        self.record.len()
+    }
+
+    /// Returns `true` if the record is empty.
+    pub fn is_empty(&self) -> bool {
+        self.record.is_empty()
+    }
+
+    /// Returns the position of the current action.
+    pub fn current(&self) -> usize {
+        self.record.current()
+    }
+
+    /// Sets how the signal should be handled when the state changes.
+    ///
+    /// The previous slot is returned if it exists.
+    pub fn connect(&mut self, slot: F) -> Option<F> {
+        self.record.connect(slot)
+    }
+
+    /// Removes and returns the slot if it exists.
+    pub fn disconnect(&mut self) -> Option<F> {
+        self.record.disconnect()
+    }
+
+    /// Returns `true` if the target is in a saved state, `false` otherwise.
+    pub fn is_saved(&self) -> bool {
+        self.record.is_saved()
+    }
+}
+
+impl<A, I: PartialEq, F> CollaborativeRecord<A, I, F> {
+    /// Returns `true` if the author can undo.
+    pub fn can_undo(&self, author: &I) -> bool {
+        self.last_applied(author).is_some()
+    }
+
+    /// Returns `true` if the author can redo.
+    pub fn can_redo(&self, author: &I) -> bool {
+        self.first_undone(author).is_some()
+    }
+
+    /// Returns the index of the last action applied by the author.
+    fn last_applied(&self, author: &I) -> Option<usize> {
+        self.record
+            .entries
+            .range(..self.current())
+            .rposition(|entry| entry.action.author == *author)
+    }
+
+    /// Returns the index of the first action undone by the author.
+    fn first_undone(&self, author: &I) -> Option<usize> {
+        let current = self.current();
+        self.record
+            .entries
+            .range(current..)
+            .position(|entry| entry.action.author == *author)
+            .map(|i| current + i)
+    }
+}
+
+impl<A: Transform, I: PartialEq, F: FnMut(Signal)> CollaborativeRecord<A, I, F> {
+    /// Pushes the action made by the author on top of the record and executes its [`apply`] method.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned.
+    ///
+    /// [`apply`]: ../trait.Action.html#tymethod.apply
+    pub fn apply(&mut self, target: &mut A::Target, author: I, action: A) -> Result<A> {
+        self.record.apply(target, Authored { author, action })
+    }
+
+    /// Undoes the last action applied by the author.
+    ///
+    /// The action is transformed past the actions applied after it before it is undone.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    pub fn undo(&mut self, target: &mut A::Target, author: &I) -> Option<Result<A>> {
+        let index = self.last_applied(author)?;
+        let top = self.current() - 1;
+        for i in index..top {
+            self.swap(i);
+        }
+        self.invalidate_saved(index, top);
+        self.record.undo(target)
+    }
+
+    /// Redoes the first action undone by the author.
+    ///
+    /// The action is transformed past the actions undone before it before it is redone.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned.
+    ///
+    /// [`redo`]: ../trait.Action.html#method.redo
+    pub fn redo(&mut self, target: &mut A::Target, author: &I) -> Option<Result<A>> {
+        let index = self.first_undone(author)?;
+        let current = self.current();
+        for i in (current..index).rev() {
+            self.swap(i);
+        }
+        self.invalidate_saved(current, index);
+        self.record.redo(target)
+    }
+
+    /// Marks the target as currently being in a saved or unsaved state.
+    pub fn set_saved(&mut self, saved: bool) {
+        self.record.set_saved(saved);
+    }
+
+    /// Removes all actions from the record without undoing them.
+    pub fn clear(&mut self) {
+        self.record.clear();
+    }
+
+    /// Swaps the actions at `i` and `i + 1`, transforming them so that
+    /// applying them in the new order gives the same result.
+    fn swap(&mut self, i: usize) {
+        self.record.entries.swap(i, i + 1);
+        let mut entries = self.record.entries.range_mut(i..i + 2);
+        let later = entries.next().unwrap();
+        let earlier = entries.next().unwrap();
+        // The record is never grouping, so every entry holds a single action.
+        debug_assert!(!later.is_group() && !earlier.is_group());
+        let later = &mut later.action.action;
+        let earlier = &mut earlier.action.action;
+        later.exclude(earlier);
+        earlier.include(later);
+    }
+
+    /// Removes the saved state if it was between the positions `from` and `to`,
+    /// since the state no longer exists after the actions have been reordered.
+    fn invalidate_saved(&mut self, from: usize, to: usize) {
+        self.record.saved = self
+            .record
+            .saved
+            .filter(|&saved| saved <= from || saved > to);
+    }
+}
+
+impl<A, I> Default for CollaborativeRecord<A, I> {
+    fn default() -> CollaborativeRecord<A, I> {
+        CollaborativeRecord::new()
+    }
+}
+
+impl<A: fmt::Debug, I: fmt::Debug, F> fmt::Debug for CollaborativeRecord<A, I, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CollaborativeRecord")
+            .field("record", &self.record)
+            .finish()
+    }
+}
+
+/// An action tagged with the author that applied it.
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+struct Authored<A, I> {
+    author: I,
+    action: A,
+}
+
+impl<A: Action, I: PartialEq> Action for Authored<A, I> {
+    type Target = A::Target;
+    type Output = A::Output;
+    type Error = A::Error;
+
+    fn apply(&mut self, target: &mut A::Target) -> Result<A> {
+        self.action.apply(target)
+    }
+
+    fn undo(&mut self, target: &mut A::Target) -> Result<A> {
+        self.action.undo(target)
+    }
+
+    fn redo(&mut self, target: &mut A::Target) -> Result<A> {
+        self.action.redo(target)
+    }
+
+    fn merge(&mut self, other: Self) -> Merged<Self> {
+        // Only actions made by the same author are merged.
+        if self.author != other.author {
+            return Merged::No(other);
+        }
+        let Authored { author, action } = other;
+        match self.action.merge(action) {
+            Merged::Yes => Merged::Yes,
+            Merged::No(action) => Merged::No(Authored { author, action }),
+            Merged::Annul => Merged::Annul,
+        }
+    }
+
+    fn merge_key(&self) -> Option<u64> {
+        self.action.merge_key()
+    }
+
+    fn size_hint(&self) -> usize {
+        self.action.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use alloc::string::String;
+
+    struct Insert(usize, char);
+
+    impl Action for Insert {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Insert> {
+            s.insert(self.0, self.1);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Insert> {
+            s.remove(self.0);
+            Ok(())
+        }
+    }
+
+    impl Transform for Insert {
+        fn include(&mut self, other: &Insert) {
+            if other.0 < self.0 || (other.0 == self.0 && other.1 < self.1) {
+                self.0 += 1;
+            }
+        }
+
+        fn exclude(&mut self, other: &Insert) {
+            if other.0 < self.0 {
+                self.0 -= 1;
+            }
+        }
+    }
+
+    const ALICE: u8 = 1;
+    const BOB: u8 = 2;
+
+    #[test]
+    fn undo_keeps_actions_of_other_authors() {
+        let mut target = String::new();
+        let mut record = CollaborativeRecord::new();
+        record.apply(&mut target, ALICE, Insert(0, 'a')).unwrap();
+        record.apply(&mut target, ALICE, Insert(1, 'b')).unwrap();
+        record.apply(&mut target, BOB, Insert(0, 'x')).unwrap();
+        assert_eq!(target, "xab");
+        record.undo(&mut target, &ALICE).unwrap().unwrap();
+        assert_eq!(target, "xa");
+        record.undo(&mut target, &BOB).unwrap().unwrap();
+        assert_eq!(target, "a");
+        record.redo(&mut target, &ALICE).unwrap().unwrap();
+        assert_eq!(target, "ab");
+        record.redo(&mut target, &BOB).unwrap().unwrap();
+        assert_eq!(target, "xab");
+    }
+
+    #[test]
+    fn authors_only_undo_their_own_actions() {
+        let mut target = String::new();
+        let mut record = CollaborativeRecord::new();
+        record.apply(&mut target, ALICE, Insert(0, 'a')).unwrap();
+        record.apply(&mut target, BOB, Insert(1, 'b')).unwrap();
+        record.apply(&mut target, BOB, Insert(2, 'c')).unwrap();
+        assert!(record.can_undo(&ALICE));
+        record.undo(&mut target, &ALICE).unwrap().unwrap();
+        assert_eq!(target, "bc");
+        assert!(!record.can_undo(&ALICE));
+        assert!(record.can_redo(&ALICE));
+        assert!(record.undo(&mut target, &ALICE).is_none());
+        assert!(!record.can_redo(&BOB));
+        assert!(record.redo(&mut target, &BOB).is_none());
+        record.undo(&mut target, &BOB).unwrap().unwrap();
+        record.undo(&mut target, &BOB).unwrap().unwrap();
+        assert_eq!(target, "");
+        assert!(!record.can_undo(&BOB));
+        record.redo(&mut target, &ALICE).unwrap().unwrap();
+        assert_eq!(target, "a");
+        record.redo(&mut target, &BOB).unwrap().unwrap();
+        record.redo(&mut target, &BOB).unwrap().unwrap();
+        assert_eq!(target, "abc");
+    }
+
+    #[test]
+    fn peers_converge() {
+        // Each peer applies its own actions directly, and the actions of the other peer
+        // after transforming them past the concurrent local actions.
+        let mut alice = String::new();
+        let mut bob = String::new();
+        let mut alice_record = CollaborativeRecord::new();
+        let mut bob_record = CollaborativeRecord::new();
+        alice_record
+            .apply(&mut alice, ALICE, Insert(0, 'a'))
+            .unwrap();
+        bob_record.apply(&mut bob, BOB, Insert(0, 'b')).unwrap();
+
+        let mut from_bob = Insert(0, 'b');
+        from_bob.include(&Insert(0, 'a'));
+        alice_record.apply(&mut alice, BOB, from_bob).unwrap();
+        let mut from_alice = Insert(0, 'a');
+        from_alice.include(&Insert(0, 'b'));
+        bob_record.apply(&mut bob, ALICE, from_alice).unwrap();
+        assert_eq!(alice, "ab");
+        assert_eq!(bob, "ab");
+
+        alice_record
+            .apply(&mut alice, ALICE, Insert(2, 'c'))
+            .unwrap();
+        bob_record.apply(&mut bob, ALICE, Insert(2, 'c')).unwrap();
+        assert_eq!(alice, bob);
+
+        // Undoing is sent to the other peer as the author whose action is undone.
+        alice_record.undo(&mut alice, &ALICE).unwrap().unwrap();
+        bob_record.undo(&mut bob, &ALICE).unwrap().unwrap();
+        assert_eq!(alice, "ab");
+        assert_eq!(alice, bob);
+        alice_record.undo(&mut alice, &ALICE).unwrap().unwrap();
+        bob_record.undo(&mut bob, &ALICE).unwrap().unwrap();
+        assert_eq!(alice, "b");
+        assert_eq!(alice, bob);
+        bob_record.undo(&mut bob, &BOB).unwrap().unwrap();
+        alice_record.undo(&mut alice, &BOB).unwrap().unwrap();
+        assert_eq!(alice, "");
+        assert_eq!(alice, bob);
+        alice_record.redo(&mut alice, &ALICE).unwrap().unwrap();
+        bob_record.redo(&mut bob, &ALICE).unwrap().unwrap();
+        assert_eq!(alice, "a");
+        assert_eq!(alice, bob);
+    }
+
+    #[test]
+    fn reordering_invalidates_saved_state() {
+        let mut target = String::new();
+        let mut record = CollaborativeRecord::new();
+        record.apply(&mut target, ALICE, Insert(0, 'a')).unwrap();
+        record.set_saved(true);
+        record.apply(&mut target, BOB, Insert(1, 'b')).unwrap();
+        record.undo(&mut target, &BOB).unwrap().unwrap();
+        assert!(record.is_saved());
+        record.redo(&mut target, &BOB).unwrap().unwrap();
+        record.undo(&mut target, &ALICE).unwrap().unwrap();
+        assert_eq!(target, "b");
+        assert!(!record.is_saved());
+    }
+}
@@ -0,0 +1,327 @@
+//! Conversions shared by the serialization formats.
+//!
+//! The formats store the data structures in their own types. They are converted
+//! to and from the data types defined here, so the stored data is checked in one place
+//! before it is turned into a data structure.
+
+#[cfg(feature = "alloc")]
+use crate::history::Branch;
+#[cfg(feature = "alloc")]
+use crate::record::Builder;
+use crate::Entry;
+#[cfg(feature = "alloc")]
+use crate::{At, History, Record};
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+use core::fmt;
+#[cfg(all(feature = "alloc", feature = "serde"))]
+use serde::{Deserialize, Serialize};
+
+/// A record as it is stored.
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct RecordData<A> {
+    /// The entries, from the oldest to the newest.
+    pub entries: Vec<EntryData<A>>,
+    /// The position of the record.
+    pub current: usize,
+    /// The limit of the record.
+    pub limit: usize,
+    /// The position where the target was saved, if any.
+    pub saved: Option<usize>,
+}
+
+/// A history as it is stored.
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct HistoryData<A> {
+    /// The current branch.
+    pub record: RecordData<A>,
+    /// The id of the current branch.
+    pub branch: usize,
+    /// The id of the next branch that is created.
+    pub next: usize,
+    /// The branch and position where the target was saved, if it is not in the current branch.
+    pub saved: Option<(usize, usize)>,
+    /// The branches that are not the current branch.
+    pub branches: Vec<BranchData<A>>,
+}
+
+/// A branch as it is stored.
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct BranchData<A> {
+    /// The id of the branch.
+    pub id: usize,
+    /// The branch and position the branch is attached to.
+    pub parent: (usize, usize),
+    /// The entries of the branch after the parent position.
+    pub entries: Vec<EntryData<A>>,
+}
+
+/// An entry as it is stored.
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+pub struct EntryData<A> {
+    /// The action.
+    pub action: A,
+    /// The actions that were applied after `action` as part of the same group.
+    pub group: Vec<A>,
+    /// The label of the group.
+    pub label: Option<String>,
+    /// When the entry was created, in nanoseconds since the Unix epoch.
+    pub timestamp: Option<i64>,
+    /// When the entry was last modified by a merge, in nanoseconds since the Unix epoch.
+    pub modified: Option<i64>,
+}
+
+/// The reason the stored data can not be turned into a data structure.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Invalid {
+    /// The limit is `0`.
+    Limit,
+    /// A number does not fit in a `usize`.
+    #[cfg(feature = "rkyv")]
+    Overflow,
+    /// There are more entries than the limit.
+    Len,
+    /// A position is larger than the number of entries.
+    OutOfRange(usize),
+    /// The branch id is the current branch, or not smaller than the next id.
+    BranchId(usize),
+    /// The branch id is used by more than one branch.
+    DuplicateBranch(usize),
+    /// The parent branch of the branch does not exist.
+    UnknownParent(usize),
+    /// The branch does not lead back to the current branch.
+    Detached(usize),
+    /// The target is saved in both the current branch and another branch.
+    Saved,
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Invalid::Limit => f.write_str("limit can not be `0`"),
+            #[cfg(feature = "rkyv")]
+            Invalid::Overflow => f.write_str("number does not fit in a `usize`"),
+            Invalid::Len => f.write_str("there are more entries than the limit"),
+            Invalid::OutOfRange(position) => write!(f, "position {position} is out of range"),
+            Invalid::BranchId(id) => write!(f, "branch id {id} is invalid"),
+            Invalid::DuplicateBranch(id) => write!(f, "branch id {id} is not unique"),
+            Invalid::UnknownParent(id) => write!(f, "parent of branch {id} does not exist"),
+            Invalid::Detached(id) => write!(f, "branch {id} does not lead to the current branch"),
+            Invalid::Saved => f.write_str("target is saved in more than one position"),
+        }
+    }
+}
+
+impl core::error::Error for Invalid {}
+
+/// Checks that the positions are not larger than `len`, and returns the first one that is.
+pub(crate) fn check_positions(
+    len: usize,
+    current: usize,
+    saved: Option<usize>,
+) -> Result<(), usize> {
+    if current > len {
+        return Err(current);
+    }
+    match saved {
+        Some(saved) if saved > len => Err(saved),
+        _ => Ok(()),
+    }
+}
+
+/// Returns an entry with the stored parts.
+///
+/// The timestamps are in nanoseconds since the Unix epoch,
+/// and the entry is created now if `timestamp` is `None`.
+pub(crate) fn entry<A>(
+    action: A,
+    #[cfg(feature = "alloc")] group: Vec<A>,
+    #[cfg(feature = "alloc")] label: Option<String>,
+    timestamp: Option<i64>,
+    modified: Option<i64>,
+) -> Entry<A> {
+    #[cfg(not(feature = "chrono"))]
+    let _ = (timestamp, modified);
+    Entry {
+        action,
+        #[cfg(feature = "alloc")]
+        group,
+        #[cfg(feature = "alloc")]
+        label,
+        #[cfg(feature = "chrono")]
+        timestamp: timestamp.map_or_else(Utc::now, DateTime::from_timestamp_nanos),
+        #[cfg(feature = "chrono")]
+        modified: modified.map(DateTime::from_timestamp_nanos),
+    }
+}
+
+impl<A> Entry<A> {
+    /// Returns when the entry was created and last modified, in nanoseconds since the Unix epoch.
+    pub(crate) fn timestamps(&self) -> (Option<i64>, Option<i64>) {
+        #[cfg(feature = "chrono")]
+        return (
+            self.timestamp.timestamp_nanos_opt(),
+            self.modified
+                .and_then(|modified| modified.timestamp_nanos_opt()),
+        );
+        #[cfg(not(feature = "chrono"))]
+        (None, None)
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn entry_data<A>(entry: &Entry<A>) -> EntryData<&A> {
+    let (timestamp, modified) = entry.timestamps();
+    EntryData {
+        action: &entry.action,
+        group: entry.group.iter().collect(),
+        label: entry.label.clone(),
+        timestamp,
+        modified,
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn record_data<A, F>(record: &Record<A, F>) -> RecordData<&A> {
+    RecordData {
+        entries: record.entries.iter().map(entry_data).collect(),
+        current: record.current,
+        limit: record.limit(),
+        saved: record.saved,
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn history_data<A, F>(history: &History<A, F>) -> HistoryData<&A> {
+    HistoryData {
+        record: record_data(&history.record),
+        branch: history.root,
+        next: history.next,
+        saved: history.saved.map(|at| (at.branch, at.current)),
+        branches: history
+            .branches
+            .iter()
+            .map(|(&id, branch)| BranchData {
+                id,
+                parent: (branch.parent.branch, branch.parent.current),
+                entries: branch.entries.iter().map(entry_data).collect(),
+            })
+            .collect(),
+    }
+}
+
+#[cfg(feature = "alloc")]
+fn into_entry<A>(data: EntryData<A>) -> Entry<A> {
+    entry(
+        data.action,
+        data.group,
+        data.label,
+        data.timestamp,
+        data.modified,
+    )
+}
+
+/// Checks the data and turns it into a record.
+#[cfg(feature = "alloc")]
+pub(crate) fn into_record<A, F>(data: RecordData<A>) -> Result<Record<A, F>, Invalid> {
+    let len = data.entries.len();
+    if data.limit == 0 {
+        return Err(Invalid::Limit);
+    }
+    if len > data.limit {
+        return Err(Invalid::Len);
+    }
+    check_positions(len, data.current, data.saved).map_err(Invalid::OutOfRange)?;
+    let mut record = Builder::new().limit(data.limit).capacity(len).build();
+    record
+        .entries
+        .extend(data.entries.into_iter().map(into_entry));
+    record.current = data.current;
+    record.saved = data.saved;
+    Ok(record)
+}
+
+/// Checks the data and turns it into a history.
+#[cfg(feature = "alloc")]
+pub(crate) fn into_history<A, F>(data: HistoryData<A>) -> Result<History<A, F>, Invalid> {
+    check_branches(&data)?;
+    let mut history = History::from(into_record(data.record)?);
+    history.root = data.branch;
+    history.next = data.next;
+    history.saved = data.saved.map(|(branch, current)| At::new(branch, current));
+    for branch in data.branches {
+        let (parent, current) = branch.parent;
+        let entries = branch.entries.into_iter().map(into_entry).collect();
+        history.branches.insert(
+            branch.id,
+            Branch {
+                parent: At::new(parent, current),
+                entries,
+            },
+        );
+    }
+    Ok(history)
+}
+
+/// Checks that the branches form a tree with the current branch as the root,
+/// and that the positions in the branches are in range.
+#[cfg(feature = "alloc")]
+fn check_branches<A>(data: &HistoryData<A>) -> Result<(), Invalid> {
+    let root = data.branch;
+    if root >= data.next {
+        return Err(Invalid::BranchId(root));
+    }
+    // The parent, and the first and last position of every branch.
+    let mut ranges = BTreeMap::new();
+    ranges.insert(root, (root, 0, data.record.entries.len()));
+    for branch in &data.branches {
+        if branch.id == root || branch.id >= data.next {
+            return Err(Invalid::BranchId(branch.id));
+        }
+        let (parent, start) = branch.parent;
+        let range = (parent, start, start + branch.entries.len());
+        if ranges.insert(branch.id, range).is_some() {
+            return Err(Invalid::DuplicateBranch(branch.id));
+        }
+    }
+    for branch in &data.branches {
+        let (parent, at) = branch.parent;
+        let &(_, start, end) = ranges
+            .get(&parent)
+            .ok_or(Invalid::UnknownParent(branch.id))?;
+        if at < start || at > end {
+            return Err(Invalid::OutOfRange(at));
+        }
+        // Every branch must lead back to the root, which can take at most one step per branch.
+        let mut id = parent;
+        for _ in 0..data.branches.len() {
+            if id == root {
+                break;
+            }
+            id = ranges[&id].0;
+        }
+        if id != root {
+            return Err(Invalid::Detached(branch.id));
+        }
+    }
+    if let Some((branch, at)) = data.saved {
+        if data.record.saved.is_some() {
+            return Err(Invalid::Saved);
+        }
+        match ranges.get(&branch) {
+            Some(&(_, _, end)) if at <= end => (),
+            _ => return Err(Invalid::OutOfRange(at)),
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,13 @@
 //! A history of actions.
 
+use crate::intercept::Intercept;
+use crate::layer::{Layer, Stack};
+use crate::merge::{MergePolicy, Never, Policy};
 use crate::record::Builder as RBuilder;
-use crate::{Action, At, Entry, Format, Record, Result, Signal};
+use crate::{
+    failed_at, Action, At, Entry, Error, Format, Record, Result, RollbackError, Signal,
+    Subscription, UndoAtError,
+};
 use alloc::{
     boxed::Box,
     collections::{BTreeMap, VecDeque},
@@ -9,7 +15,12 @@ use alloc::{
     vec,
     vec::Vec,
 };
-use core::fmt::{self, Write};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+use core::{
+    fmt::{self, Write},
+    mem,
+};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +29,9 @@ use serde::{Deserialize, Serialize};
 /// Unlike [Record](struct.Record.html) which maintains a linear undo history, History maintains an undo tree
 /// containing every edit made to the target.
 ///
+/// The serde implementations store the branches and the record the same way as
+/// [Record](struct.Record.html), so the same settings are skipped.
+///
 /// # Examples
 /// ```
 /// # use undo::History;
@@ -44,8 +58,8 @@ use serde::{Deserialize, Serialize};
 )]
 #[derive(Clone)]
 pub struct History<A, F = Box<dyn FnMut(Signal)>> {
-    root: usize,
-    next: usize,
+    pub(crate) root: usize,
+    pub(crate) next: usize,
     pub(crate) saved: Option<At>,
     pub(crate) record: Record<A, F>,
     pub(crate) branches: BTreeMap<usize, Branch<A>>,
@@ -92,6 +106,11 @@ impl<A, F> History<A, F> {
         self.record.limit()
     }
 
+    /// Returns the memory budget of the history in bytes, if any.
+    pub fn budget(&self) -> Option<usize> {
+        self.record.budget()
+    }
+
     /// Sets how the signal should be handled when the state changes.
     ///
     /// The previous slot is returned if it exists.
@@ -104,6 +123,23 @@ impl<A, F> History<A, F> {
         self.record.disconnect()
     }
 
+    /// Subscribes a listener that is called with every signal, in addition to the slot.
+    ///
+    /// The listener must be `Send` and `Sync` so the data structure can still be shared between threads.
+    /// The returned handle can be used to [`unsubscribe`] the listener.
+    ///
+    /// [`unsubscribe`]: struct.History.html#method.unsubscribe
+    pub fn subscribe(&mut self, f: impl FnMut(Signal) + Send + Sync + 'static) -> Subscription {
+        self.record.subscribe(f)
+    }
+
+    /// Unsubscribes the listener.
+    ///
+    /// Returns `true` if the listener was subscribed.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        self.record.unsubscribe(subscription)
+    }
+
     /// Returns `true` if the target is in a saved state, `false` otherwise.
     pub fn is_saved(&self) -> bool {
         self.record.is_saved()
@@ -129,18 +165,31 @@ impl<A, F> History<A, F> {
         self.record.current()
     }
 
+    /// Returns `true` if a group has been begun but not yet ended.
+    pub fn is_grouping(&self) -> bool {
+        self.record.is_grouping()
+    }
+
+    /// Begins a group of actions.
+    ///
+    /// The methods that move in the history end all open groups before moving.
+    /// See [`Record::begin_group`](../record/struct.Record.html#method.begin_group) for more information.
+    pub fn begin_group(&mut self, label: Option<String>) {
+        self.record.begin_group(label);
+    }
+
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<A, F> {
+    pub fn queue(&mut self) -> Queue<'_, A, F> {
         Queue::from(self)
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<A, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, A, F> {
         Checkpoint::from(self)
     }
 
     /// Returns a structure for configurable formatting of the history.
-    pub fn display(&self) -> Display<A, F> {
+    pub fn display(&self) -> Display<'_, A, F> {
         Display::from(self)
     }
 
@@ -156,58 +205,251 @@ impl<A: Action, F: FnMut(Signal)> History<A, F> {
     /// If an error occur when executing [`apply`] the error is returned.
     ///
     /// [`apply`]: trait.Action.html#tymethod.apply
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "apply",
+            level = "debug",
+            skip_all,
+            fields(branch = self.root, current = self.current())
+        )
+    )]
     pub fn apply(&mut self, target: &mut A::Target, action: A) -> Result<A> {
         let at = self.at();
         let saved = self.record.saved.filter(|&saved| saved > at.current);
-        let (output, merged, tail) = self.record.__apply(target, action)?;
-        // Check if the limit has been reached.
-        if !merged && at.current == self.current() {
-            let root = self.branch();
-            self.rm_child(root, 0);
-            self.branches
-                .values_mut()
-                .filter(|branch| branch.parent.branch == root)
-                .for_each(|branch| branch.parent.current -= 1);
+        let (output, evicted, tail) = self.record.__apply(target, action)?;
+        self.pushed(at, saved, evicted, tail);
+        Ok(output)
+    }
+
+    /// Ends the most recently begun group.
+    ///
+    /// See [`Record::end_group`](../record/struct.Record.html#method.end_group) for more information.
+    pub fn end_group(&mut self) {
+        let at = self.at();
+        let saved = self.record.saved.filter(|&saved| saved > at.current);
+        if let Some((evicted, tail)) = self.record.__end_group() {
+            self.pushed(at, saved, evicted, tail);
         }
-        // Handle new branch.
-        if !tail.is_empty() {
+    }
+
+    fn end_groups(&mut self) {
+        while self.is_grouping() {
+            self.end_group();
+        }
+    }
+
+    /// Updates the branches after an entry has been pushed onto the record at `at`.
+    fn pushed(&mut self, at: At, saved: Option<usize>, evicted: usize, tail: VecDeque<Entry<A>>) {
+        self.evicted(evicted);
+        // Handle new branch, unless the position it is attached to has been evicted.
+        let current = at.current.checked_sub(evicted);
+        if let Some(current) = current.filter(|_| !tail.is_empty()) {
+            let saved = saved.and_then(|saved| saved.checked_sub(evicted));
             let new = self.next;
             self.next += 1;
             self.branches
-                .insert(at.branch, Branch::new(new, at.current, tail));
-            self.set_root(new, at.current, saved);
+                .insert(at.branch, Branch::new(new, current, tail));
+            self.set_root(new, current, saved);
+        }
+    }
+
+    /// Updates the branches after entries have been evicted from the bottom of the record.
+    fn evicted(&mut self, evicted: usize) {
+        // Remove the branches that were attached to the evicted entries,
+        // and move the remaining ones down since the positions are shifted.
+        let root = self.branch();
+        for _ in 0..evicted {
+            self.rm_child(root, 0);
+            self.branches
+                .values_mut()
+                .for_each(|branch| branch.parent.current -= 1);
+            self.saved = self.saved.and_then(|saved| {
+                let current = saved.current.checked_sub(1)?;
+                Some(At::new(saved.branch, current))
+            });
         }
-        Ok(output)
     }
 
     /// Calls the [`undo`] method for the active action
     /// and sets the previous one as the new active one.
     ///
+    /// Any open [group](struct.History.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
     /// # Errors
     /// If an error occur when executing [`undo`] the error is returned.
     ///
     /// [`undo`]: trait.Action.html#tymethod.undo
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "undo",
+            level = "debug",
+            skip_all,
+            fields(branch = self.root, current = self.current())
+        )
+    )]
     pub fn undo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.end_groups();
         self.record.undo(target)
     }
 
     /// Calls the [`redo`] method for the active action
     /// and sets the next one as the new active one.
     ///
+    /// Any open [group](struct.History.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
     /// # Errors
     /// If an error occur when executing [`redo`] the error is returned.
     ///
     /// [`redo`]: trait.Action.html#method.redo
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "redo",
+            level = "debug",
+            skip_all,
+            fields(branch = self.root, current = self.current())
+        )
+    )]
     pub fn redo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.end_groups();
         self.record.redo(target)
     }
 
+    /// Like [`undo`], but returns an error instead of `None` if there is nothing to undo.
+    ///
+    /// # Errors
+    /// Returns [`Error::NothingToUndo`] if there is nothing to undo,
+    /// or [`Error::Action`] with the index of the action if an error occur when executing [`undo`].
+    ///
+    /// [`undo`]: struct.History.html#method.undo
+    /// [`Error::NothingToUndo`]: ../enum.Error.html#variant.NothingToUndo
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    pub fn try_undo(
+        &mut self,
+        target: &mut A::Target,
+    ) -> core::result::Result<A::Output, Error<A::Error>> {
+        self.end_groups();
+        let current = self.current();
+        self.undo(target)
+            .ok_or(Error::NothingToUndo)?
+            .map_err(|error| Error::Action(error, current - 1))
+    }
+
+    /// Like [`redo`], but returns an error instead of `None` if there is nothing to redo.
+    ///
+    /// # Errors
+    /// Returns [`Error::NothingToRedo`] if there is nothing to redo,
+    /// or [`Error::Action`] with the index of the action if an error occur when executing [`redo`].
+    ///
+    /// [`redo`]: struct.History.html#method.redo
+    /// [`Error::NothingToRedo`]: ../enum.Error.html#variant.NothingToRedo
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    pub fn try_redo(
+        &mut self,
+        target: &mut A::Target,
+    ) -> core::result::Result<A::Output, Error<A::Error>> {
+        self.end_groups();
+        let current = self.current();
+        self.redo(target)
+            .ok_or(Error::NothingToRedo)?
+            .map_err(|error| Error::Action(error, current))
+    }
+
+    /// Undoes the action at `index` in the current branch without undoing the actions applied after it.
+    ///
+    /// Any open [group](struct.History.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
+    /// See [`Record::undo_at`](../record/struct.Record.html#method.undo_at) for more information.
+    ///
+    /// # Errors
+    /// In addition to the errors returned by the record, a `Branch` error is returned
+    /// if a branch starts between the action and the top of the stack.
+    pub fn undo_at(
+        &mut self,
+        target: &mut A::Target,
+        index: usize,
+    ) -> Option<core::result::Result<A::Output, UndoAtError<A::Error>>> {
+        self.end_groups();
+        let root = self.branch();
+        let current = self.current();
+        if let Some(&id) = self.branches.iter().find_map(|(id, branch)| {
+            let at = branch.parent;
+            (at.branch == root && at.current > index && at.current < current).then_some(id)
+        }) {
+            return Some(Err(UndoAtError::Branch(id)));
+        }
+        self.record.undo_at(target, index)
+    }
+
+    /// Returns the approximate number of bytes used by the entries in the current branch.
+    ///
+    /// The other branches are not counted, the same as for the [budget] which only
+    /// applies to the current branch.
+    /// See [`Record::memory_usage`](../record/struct.Record.html#method.memory_usage) for more information.
+    ///
+    /// [budget]: struct.Builder.html#method.budget
+    pub fn memory_usage(&self) -> usize {
+        self.record.memory_usage()
+    }
+
+    /// Removes the actions that were last modified before `time` from the bottom of the
+    /// current branch, and returns the number of removed actions.
+    ///
+    /// The branches that were attached to the removed actions are removed too.
+    /// See [`Record::prune_older_than`] for more information.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    ///
+    /// [`Record::prune_older_than`]: ../record/struct.Record.html#method.prune_older_than
+    #[cfg(feature = "chrono")]
+    pub fn prune_older_than(&mut self, time: DateTime<Utc>) -> usize {
+        let pruned = self.record.prune_older_than(time);
+        self.evicted(pruned);
+        pruned
+    }
+
     /// Marks the target as currently being in a saved or unsaved state.
     pub fn set_saved(&mut self, saved: bool) {
         self.saved = None;
         self.record.set_saved(saved);
     }
 
+    /// Calls `f` with the history while holding back the signals,
+    /// and then only emits the net changes in the state.
+    ///
+    /// See [`Record::batch_signals`](../record/struct.Record.html#method.batch_signals) for more information.
+    pub fn batch_signals<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let branch = self.branch();
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let muted = self.record.slot.mute(true);
+        let r = f(self);
+        self.record.slot.mute(muted);
+        let to = self.branch();
+        let can_undo = self.can_undo();
+        let can_redo = self.can_redo();
+        let is_saved = self.is_saved();
+        self.record
+            .slot
+            .emit_if(branch != to, Signal::BranchChanged { from: branch, to });
+        self.record
+            .slot
+            .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
+        self.record
+            .slot
+            .emit_if(could_redo != can_redo, Signal::Redo(can_redo));
+        self.record
+            .slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
+        r
+    }
+
     /// Removes all actions from the history without undoing them.
     pub fn clear(&mut self) {
         self.root = 0;
@@ -233,6 +475,12 @@ impl<A: Action, F: FnMut(Signal)> History<A, F> {
         let old = self.branch();
         self.root = root;
         debug_assert_ne!(old, root);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(from = old, to = root, "branch changed");
+        self.record.slot.emit(Signal::BranchChanged {
+            from: old,
+            to: root,
+        });
         // Handle the child branches.
         self.branches
             .values_mut()
@@ -300,39 +548,66 @@ impl<A: Action, F: FnMut(Signal)> History<A, F> {
         }
         Some(path.into_iter().rev())
     }
-}
 
-impl<A: Action<Output = ()>, F: FnMut(Signal)> History<A, F> {
-    /// Repeatedly calls [`undo`] or [`redo`] until the action in `branch` at `current` is reached.
+    /// Like [`go_to`], but calls `f` with the output of each action that is undone, redone
+    /// or applied.
     ///
-    /// # Errors
-    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    /// [`go_to`]: struct.History.html#method.go_to
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "go_to",
+            level = "debug",
+            skip_all,
+            fields(from_branch = self.root, from = self.current(), to_branch = branch, to = current)
+        )
+    )]
+    pub fn go_to_with(
+        &mut self,
+        target: &mut A::Target,
+        branch: usize,
+        current: usize,
+        f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        let result = self.walk_with(target, branch, current, f)?;
+        Some(result.map_err(|(error, _)| error))
+    }
+
+    /// Like [`go_to_with`], but also returns the index of the action that failed.
     ///
-    /// [`undo`]: trait.Action.html#tymethod.undo
-    /// [`redo`]: trait.Action.html#method.redo
-    pub fn go_to(
+    /// [`go_to_with`]: struct.History.html#method.go_to_with
+    fn walk_with(
         &mut self,
         target: &mut A::Target,
         branch: usize,
         current: usize,
-    ) -> Option<Result<A>> {
+        mut f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), (A::Error, usize)>> {
+        self.end_groups();
         let root = self.root;
         if root == branch {
-            return self.record.go_to(target, current);
+            return self.walk_record(target, current, f);
         }
         // Walk the path from `root` to `branch`.
         for (new, branch) in self.mk_path(branch)? {
             // Walk to `branch.current` either by undoing or redoing.
-            if let Err(err) = self.record.go_to(target, branch.parent.current).unwrap() {
+            if let Err(err) = self
+                .walk_record(target, branch.parent.current, &mut f)
+                .unwrap()
+            {
                 return Some(Err(err));
             }
-            // Apply the actions in the branch and move older actions into their own branch.
-            for entry in branch.entries {
+            // Redo the actions in the branch and move older actions into their own branch.
+            for mut entry in branch.entries {
                 let current = self.current();
                 let saved = self.record.saved.filter(|&saved| saved > current);
-                let entries = match self.record.__apply(target, entry.action) {
-                    Ok((_, _, entries)) => entries,
-                    Err(err) => return Some(Err(err)),
+                match entry.redo(target, &self.record.guard, current) {
+                    Ok(output) => f(output),
+                    Err(err) => return Some(Err((err, current))),
+                }
+                let (_, entries) = match self.record.push(entry) {
+                    Ok(pushed) => pushed,
+                    Err(err) => return Some(Err((err, current))),
                 };
                 if !entries.is_empty() {
                     self.branches
@@ -341,7 +616,157 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> History<A, F> {
                 }
             }
         }
-        self.record.go_to(target, current)
+        self.walk_record(target, current, f)
+    }
+
+    /// Walks to `current` in the record and returns the index of the action that failed.
+    fn walk_record(
+        &mut self,
+        target: &mut A::Target,
+        current: usize,
+        f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), (A::Error, usize)>> {
+        let result = self.record.go_to_with(target, current, f)?;
+        Some(result.map_err(|error| (error, failed_at(self.current(), current))))
+    }
+}
+
+impl<A: Action<Output = ()>, F: FnMut(Signal)> History<A, F> {
+    /// Repeatedly calls [`undo`] or [`redo`] until the action in `branch` at `current` is reached.
+    ///
+    /// Any open [group](struct.History.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Action.html#tymethod.undo
+    /// [`redo`]: trait.Action.html#method.redo
+    pub fn go_to(
+        &mut self,
+        target: &mut A::Target,
+        branch: usize,
+        current: usize,
+    ) -> Option<Result<A>> {
+        self.go_to_with(target, branch, current, |()| ())
+    }
+
+    /// Like [`go_to`], but rolls back to the position it started from if an error occurs.
+    ///
+    /// The actions are moved between the branches without being removed,
+    /// so the branches are restored as they were when rolling back.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned
+    /// together with any error that occurred during the rollback.
+    ///
+    /// [`go_to`]: struct.History.html#method.go_to
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    /// [`redo`]: ../trait.Action.html#method.redo
+    pub fn go_to_atomic(
+        &mut self,
+        target: &mut A::Target,
+        branch: usize,
+        current: usize,
+    ) -> Option<core::result::Result<(), RollbackError<A::Error>>> {
+        self.end_groups();
+        let len = if branch == self.root {
+            self.len()
+        } else {
+            let branch = self.branches.get(&branch)?;
+            branch.parent.current + branch.entries.len()
+        };
+        if current > len {
+            return None;
+        }
+        let start = self.at();
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let muted = self.record.slot.mute(true);
+        let result = self
+            .walk_to(target, At::new(branch, current))
+            .map_err(|error| {
+                let rollback = self.walk_to(target, start).err();
+                RollbackError { error, rollback }
+            });
+        self.record.slot.mute(muted);
+        let branch = self.branch();
+        self.record.slot.emit_if(
+            start.branch != branch,
+            Signal::BranchChanged {
+                from: start.branch,
+                to: branch,
+            },
+        );
+        let can_undo = self.can_undo();
+        let can_redo = self.can_redo();
+        let is_saved = self.is_saved();
+        self.record
+            .slot
+            .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
+        self.record
+            .slot
+            .emit_if(could_redo != can_redo, Signal::Redo(can_redo));
+        self.record
+            .slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
+        Some(result)
+    }
+
+    /// Like [`go_to`], but returns an error instead of `None` if `branch` or `current` is invalid.
+    ///
+    /// # Errors
+    /// Returns [`Error::UnknownBranch`] if `branch` does not exist,
+    /// [`Error::OutOfRange`] if `current` is larger than the number of actions in the branch,
+    /// or [`Error::Action`] with the index of the action in the current branch
+    /// if an error occur when executing [`undo`] or [`redo`].
+    ///
+    /// [`go_to`]: struct.History.html#method.go_to
+    /// [`Error::UnknownBranch`]: ../enum.Error.html#variant.UnknownBranch
+    /// [`Error::OutOfRange`]: ../enum.Error.html#variant.OutOfRange
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    /// [`redo`]: ../trait.Action.html#method.redo
+    pub fn try_go_to(
+        &mut self,
+        target: &mut A::Target,
+        branch: usize,
+        current: usize,
+    ) -> core::result::Result<(), Error<A::Error>> {
+        self.end_groups();
+        let len = if branch == self.root {
+            self.len()
+        } else {
+            let branch = self
+                .branches
+                .get(&branch)
+                .ok_or(Error::UnknownBranch(branch))?;
+            branch.parent.current + branch.entries.len()
+        };
+        if current > len {
+            return Err(Error::OutOfRange(current));
+        }
+        self.walk_with(target, branch, current, |()| ())
+            .unwrap()
+            .map_err(|(error, at)| Error::Action(error, at))
+    }
+
+    /// Walks to `at` by undoing and redoing actions in the record
+    /// and jumping between the branches on the path to it.
+    fn walk_to(&mut self, target: &mut A::Target, at: At) -> Result<A> {
+        let mut path = Vec::new();
+        let mut branch = at.branch;
+        while branch != self.root {
+            let parent = self.branches[&branch].parent;
+            path.push((branch, parent.current));
+            branch = parent.branch;
+        }
+        for (branch, current) in path.into_iter().rev() {
+            self.record.go_to(target, current).unwrap()?;
+            self.jump_to(branch);
+        }
+        self.record.go_to(target, at.current).unwrap()
     }
 }
 
@@ -421,16 +846,18 @@ impl<A> Branch<A> {
 /// # }
 /// ```
 #[derive(Debug)]
-pub struct Builder<F = Box<dyn FnMut(Signal)>>(RBuilder<F>);
+pub struct Builder<F = Box<dyn FnMut(Signal)>, L = ()>(RBuilder<F, L>);
 
 impl<F> Builder<F> {
     /// Returns a builder for a history.
     pub fn new() -> Builder<F> {
         Builder(RBuilder::new())
     }
+}
 
+impl<F, L> Builder<F, L> {
     /// Sets the capacity for the history.
-    pub fn capacity(self, capacity: usize) -> Builder<F> {
+    pub fn capacity(self, capacity: usize) -> Builder<F, L> {
         Builder(self.0.capacity(capacity))
     }
 
@@ -438,25 +865,85 @@ impl<F> Builder<F> {
     ///
     /// # Panics
     /// Panics if `limit` is `0`.
-    pub fn limit(self, limit: usize) -> Builder<F> {
+    pub fn limit(self, limit: usize) -> Builder<F, L> {
         Builder(self.0.limit(limit))
     }
 
+    /// Sets the memory `budget` of the history in bytes.
+    ///
+    /// The budget only applies to the current branch, which evicts its oldest entries
+    /// when it goes over budget. The entries in the other branches are not counted,
+    /// and are kept in memory however large they are. [`History::memory_usage`] reports
+    /// the same number that is checked against the budget.
+    /// See [`record::Builder::budget`] for more information.
+    ///
+    /// [`History::memory_usage`]: struct.History.html#method.memory_usage
+    ///
+    /// [`record::Builder::budget`]: ../record/struct.Builder.html#method.budget
+    pub fn budget(self, budget: usize) -> Builder<F, L> {
+        Builder(self.0.budget(budget))
+    }
+
+    /// Sets how long the actions are kept in the current branch of the history.
+    ///
+    /// See [`record::Builder::retention`] for more information.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    ///
+    /// [`record::Builder::retention`]: ../record/struct.Builder.html#method.retention
+    #[cfg(feature = "chrono")]
+    pub fn retention(self, retention: chrono::Duration) -> Builder<F, L> {
+        Builder(self.0.retention(retention))
+    }
+
     /// Sets if the target is initially in a saved state.
     /// By default the target is in a saved state.
-    pub fn saved(self, saved: bool) -> Builder<F> {
+    pub fn saved(self, saved: bool) -> Builder<F, L> {
         Builder(self.0.saved(saved))
     }
 
+    /// Sets the policy that decides if and how actions are merged.
+    ///
+    /// See [`record::Builder::merge_policy`](../record/struct.Builder.html#method.merge_policy)
+    /// for more information.
+    pub fn merge_policy(self, policy: impl MergePolicy + Send + Sync + 'static) -> Builder<F, L> {
+        Builder(self.0.merge_policy(policy))
+    }
+
+    /// Sets the interval in which consecutive actions are allowed to be merged.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    #[cfg(feature = "chrono")]
+    pub fn merge_interval(self, interval: chrono::Duration) -> Builder<F, L> {
+        Builder(self.0.merge_interval(interval))
+    }
+
+    /// Adds a layer that wraps the calls made to the actions.
+    ///
+    /// The first layer added is the outermost one.
+    pub fn layer<M>(self, layer: M) -> Builder<F, Stack<M, L>> {
+        Builder(self.0.layer(layer))
+    }
+
+    /// Adds an interceptor that decides if actions are allowed to be applied, undone or redone.
+    ///
+    /// This is a shorthand for adding the interceptor as a layer.
+    pub fn intercept<I>(self, interceptor: I) -> Builder<F, Stack<Intercept<I>, L>> {
+        Builder(self.0.intercept(interceptor))
+    }
+
     /// Builds the history.
-    pub fn build<A>(self) -> History<A, F> {
+    pub fn build<A>(self) -> History<A, F>
+    where
+        L: Layer<A> + Send + Sync + 'static,
+    {
         History::from(self.0.build())
     }
 }
 
-impl<F: FnMut(Signal)> Builder<F> {
+impl<F: FnMut(Signal), L> Builder<F, L> {
     /// Connects the slot.
-    pub fn connect(self, f: F) -> Builder<F> {
+    pub fn connect(self, f: F) -> Builder<F, L> {
         Builder(self.0.connect(f))
     }
 }
@@ -498,7 +985,7 @@ pub struct Queue<'a, A, F> {
     actions: Vec<QueueAction<A>>,
 }
 
-impl<A: Action<Output = ()>, F: FnMut(Signal)> Queue<'_, A, F> {
+impl<A: Action, F: FnMut(Signal)> Queue<'_, A, F> {
     /// Queues an `apply` action.
     pub fn apply(&mut self, action: A) {
         self.actions.push(QueueAction::Apply(action));
@@ -514,39 +1001,134 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Queue<'_, A, F> {
         self.actions.push(QueueAction::Redo);
     }
 
-    /// Applies the queued actions.
+    /// Applies the queued actions and calls `f` with the output of each of them.
     ///
     /// # Errors
     /// If an error occurs, it stops applying the actions and returns the error.
-    pub fn commit(self, target: &mut A::Target) -> Option<Result<A>> {
-        for action in self.actions {
-            let o = match action {
-                QueueAction::Apply(action) => Some(self.history.apply(target, action)),
-                QueueAction::Undo => self.history.undo(target),
-                QueueAction::Redo => self.history.redo(target),
-            };
-            match o {
-                Some(Ok(())) => (),
-                o @ Some(Err(_)) | o @ None => return o,
+    pub fn commit_with(
+        self,
+        target: &mut A::Target,
+        mut f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        let actions = self.actions;
+        self.history.batch_signals(|history| {
+            for action in actions {
+                let o = match action {
+                    QueueAction::Apply(action) => Some(history.apply(target, action)),
+                    QueueAction::Undo => history.undo(target),
+                    QueueAction::Redo => history.redo(target),
+                };
+                match o {
+                    Some(Ok(output)) => f(output),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => return None,
+                }
             }
-        }
-        Some(Ok(()))
+            Some(Ok(()))
+        })
     }
 
     /// Cancels the queued actions.
     pub fn cancel(self) {}
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<A, F> {
+    pub fn queue(&mut self) -> Queue<'_, A, F> {
         self.history.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<A, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, A, F> {
         self.history.checkpoint()
     }
 }
 
+impl<A: Action<Output = ()>, F: FnMut(Signal)> Queue<'_, A, F> {
+    /// Applies the queued actions.
+    ///
+    /// # Errors
+    /// If an error occurs, it stops applying the actions and returns the error.
+    pub fn commit(self, target: &mut A::Target) -> Option<Result<A>> {
+        self.commit_with(target, |()| ())
+    }
+
+    /// Applies all the queued actions, or none of them.
+    ///
+    /// If an error occurs, the actions that were already applied are rolled back so
+    /// both the target and the history are returned to the state they were in before the commit.
+    /// To make this possible, the actions are not merged during the commit, and entries
+    /// are only evicted after all the actions have succeeded.
+    ///
+    /// Returns `None` without applying any actions if a queued `undo` or `redo` action
+    /// can not be performed.
+    ///
+    /// # Errors
+    /// If an error occurs, it rolls back the applied actions and returns the error
+    /// together with any error that occurred during the rollback.
+    pub fn commit_atomic(
+        self,
+        target: &mut A::Target,
+    ) -> Option<core::result::Result<(), RollbackError<A::Error>>> {
+        self.history.end_groups();
+        if !self.can_commit() {
+            return None;
+        }
+        let policy = mem::replace(&mut self.history.record.policy, Policy::new(Never));
+        let actions = self.actions;
+        let result = self.history.batch_signals(|history| {
+            let eviction = history.record.suspend_eviction();
+            let mut checkpoint = history.checkpoint();
+            let mut result = Ok(());
+            for action in actions {
+                let o = match action {
+                    QueueAction::Apply(action) => checkpoint.apply(target, action),
+                    QueueAction::Undo => checkpoint.undo(target).unwrap(),
+                    QueueAction::Redo => checkpoint.redo(target).unwrap(),
+                };
+                if let Err(error) = o {
+                    let rollback = checkpoint.cancel(target).and_then(|o| o.err());
+                    result = Err(RollbackError { error, rollback });
+                    break;
+                }
+            }
+            history.record.resume_eviction(eviction);
+            if result.is_ok() {
+                let evicted = history.record.trim();
+                history.evicted(evicted);
+            }
+            result
+        });
+        self.history.record.policy = policy;
+        Some(result)
+    }
+}
+
+impl<A, F> Queue<'_, A, F> {
+    /// Returns `true` if all the queued `undo` and `redo` actions can be performed.
+    ///
+    /// The actions are neither merged nor evicted during the commit,
+    /// so each applied action adds an entry.
+    fn can_commit(&self) -> bool {
+        let mut current = self.history.current();
+        let mut len = self.history.len();
+        self.actions.iter().all(|action| match action {
+            QueueAction::Apply(_) => {
+                current += 1;
+                len = current;
+                true
+            }
+            QueueAction::Undo if current > 0 => {
+                current -= 1;
+                true
+            }
+            QueueAction::Redo if current < len => {
+                current += 1;
+                true
+            }
+            QueueAction::Undo | QueueAction::Redo => false,
+        })
+    }
+}
+
 impl<'a, A, F> From<&'a mut History<A, F>> for Queue<'a, A, F> {
     fn from(history: &'a mut History<A, F>) -> Self {
         Queue {
@@ -570,19 +1152,19 @@ pub struct Checkpoint<'a, A, F> {
     actions: Vec<CheckpointAction>,
 }
 
-impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
+impl<A: Action, F: FnMut(Signal)> Checkpoint<'_, A, F> {
     /// Calls the `apply` method.
     pub fn apply(&mut self, target: &mut A::Target, action: A) -> Result<A> {
         let branch = self.history.branch();
-        self.history.apply(target, action)?;
+        let output = self.history.apply(target, action)?;
         self.actions.push(CheckpointAction::Apply(branch));
-        Ok(())
+        Ok(output)
     }
 
     /// Calls the `undo` method.
     pub fn undo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
         match self.history.undo(target) {
-            o @ Some(Ok(())) => {
+            o @ Some(Ok(_)) => {
                 self.actions.push(CheckpointAction::Undo);
                 o
             }
@@ -593,7 +1175,7 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
     /// Calls the `redo` method.
     pub fn redo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
         match self.history.redo(target) {
-            o @ Some(Ok(())) => {
+            o @ Some(Ok(_)) => {
                 self.actions.push(CheckpointAction::Redo);
                 o
             }
@@ -604,47 +1186,73 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
     /// Commits the changes and consumes the checkpoint.
     pub fn commit(self) {}
 
-    /// Cancels the changes and consumes the checkpoint.
+    /// Cancels the changes, calls `f` with the output of each action that is
+    /// undone or redone, and consumes the checkpoint.
     ///
     /// # Errors
     /// If an error occur when canceling the changes, the error is returned
     /// and the remaining actions are not canceled.
-    pub fn cancel(self, target: &mut A::Target) -> Option<Result<A>> {
-        for action in self.actions.into_iter().rev() {
-            match action {
-                CheckpointAction::Apply(branch) => {
-                    let root = self.history.branch();
-                    self.history.jump_to(branch);
-                    if root == branch {
-                        self.history.record.entries.pop_back();
-                    } else {
-                        self.history.branches.remove(&root).unwrap();
+    pub fn cancel_with(
+        self,
+        target: &mut A::Target,
+        mut f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        let actions = self.actions;
+        self.history.batch_signals(|history| {
+            for action in actions.into_iter().rev() {
+                match action {
+                    CheckpointAction::Apply(branch) => {
+                        match history.undo(target) {
+                            Some(Ok(output)) => f(output),
+                            Some(Err(err)) => return Some(Err(err)),
+                            None => return None,
+                        }
+                        let root = history.branch();
+                        history.record.entries.pop_back();
+                        if root != branch {
+                            history.jump_to(branch);
+                            history.branches.remove(&root).unwrap();
+                            history.next = root;
+                        }
                     }
-                }
-                CheckpointAction::Undo => match self.history.redo(target) {
-                    Some(Ok(())) => (),
-                    o => return o,
-                },
-                CheckpointAction::Redo => match self.history.undo(target) {
-                    Some(Ok(())) => (),
-                    o => return o,
-                },
-            };
-        }
-        Some(Ok(()))
+                    CheckpointAction::Undo => match history.redo(target) {
+                        Some(Ok(output)) => f(output),
+                        Some(Err(err)) => return Some(Err(err)),
+                        None => return None,
+                    },
+                    CheckpointAction::Redo => match history.undo(target) {
+                        Some(Ok(output)) => f(output),
+                        Some(Err(err)) => return Some(Err(err)),
+                        None => return None,
+                    },
+                };
+            }
+            Some(Ok(()))
+        })
     }
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<A, F> {
+    pub fn queue(&mut self) -> Queue<'_, A, F> {
         self.history.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<A, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, A, F> {
         self.history.checkpoint()
     }
 }
 
+impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
+    /// Cancels the changes and consumes the checkpoint.
+    ///
+    /// # Errors
+    /// If an error occur when canceling the changes, the error is returned
+    /// and the remaining actions are not canceled.
+    pub fn cancel(self, target: &mut A::Target) -> Option<Result<A>> {
+        self.cancel_with(target, |()| ())
+    }
+}
+
 impl<'a, A, F> From<&'a mut History<A, F>> for Checkpoint<'a, A, F> {
     fn from(history: &'a mut History<A, F>) -> Self {
         Checkpoint {
@@ -886,4 +1494,357 @@ mod tests {
         history.go_to(&mut target, abnpq, 5).unwrap().unwrap();
         assert_eq!(target, "abnpq");
     }
+
+    #[test]
+    fn group() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Add('a')).unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        let ab = history.branch();
+        history.begin_group(None);
+        history.apply(&mut target, Add('c')).unwrap();
+        history.apply(&mut target, Add('d')).unwrap();
+        assert_eq!(history.branch(), ab);
+        history.end_group();
+        assert_ne!(history.branch(), ab);
+        assert_eq!(target, "acd");
+        history.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "a");
+        history.go_to(&mut target, ab, 2).unwrap().unwrap();
+        assert_eq!(target, "ab");
+        history.go_to(&mut target, ab + 1, 2).unwrap().unwrap();
+        assert_eq!(target, "acd");
+    }
+
+    /// Pushes a char, but can only be redone if the flag is set.
+    struct Flaky(char, bool);
+
+    impl Action for Flaky {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Flaky> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Flaky> {
+            s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+
+        fn redo(&mut self, s: &mut String) -> Result<Flaky> {
+            if self.1 {
+                self.apply(s)
+            } else {
+                Err("redo failed")
+            }
+        }
+    }
+
+    #[test]
+    fn try_methods() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Flaky('a', false)).unwrap();
+        history.apply(&mut target, Flaky('b', false)).unwrap();
+        history.try_undo(&mut target).unwrap();
+        assert_eq!(
+            history.try_redo(&mut target),
+            Err(Error::Action("redo failed", 1))
+        );
+        target.clear();
+        assert_eq!(
+            history.try_undo(&mut target),
+            Err(Error::Action("s is empty", 0))
+        );
+    }
+
+    #[test]
+    fn go_to_atomic() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Flaky('a', true)).unwrap();
+        history.apply(&mut target, Flaky('b', true)).unwrap();
+        history.apply(&mut target, Flaky('c', false)).unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Flaky('d', true)).unwrap();
+        history.apply(&mut target, Flaky('e', true)).unwrap();
+        assert_eq!(target, "ade");
+        assert_eq!(history.branch(), 1);
+        assert_eq!(
+            history.go_to_atomic(&mut target, 0, 3).unwrap(),
+            Err(RollbackError {
+                error: "redo failed",
+                rollback: None
+            })
+        );
+        assert_eq!(target, "ade");
+        assert_eq!(history.branch(), 1);
+        assert_eq!(history.current(), 3);
+        history.go_to_atomic(&mut target, 0, 2).unwrap().unwrap();
+        assert_eq!(target, "ab");
+        assert_eq!(history.branch(), 0);
+        history.go_to_atomic(&mut target, 1, 3).unwrap().unwrap();
+        assert_eq!(target, "ade");
+        assert!(history.go_to_atomic(&mut target, 0, 4).is_none());
+        assert!(history.go_to_atomic(&mut target, 2, 0).is_none());
+    }
+
+    /// Pushes a char, but fails if the string already has three chars.
+    struct Limited(char);
+
+    impl Action for Limited {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Limited> {
+            if s.len() >= 3 {
+                return Err("s is full");
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Limited> {
+            s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn commit_atomic() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Limited('a')).unwrap();
+        history.apply(&mut target, Limited('x')).unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+
+        let mut queue = history.queue();
+        queue.undo();
+        queue.undo();
+        assert!(queue.commit_atomic(&mut target).is_none());
+        assert_eq!(target, "a");
+
+        let mut queue = history.queue();
+        queue.apply(Limited('b'));
+        queue.apply(Limited('c'));
+        queue.undo();
+        queue.redo();
+        queue.apply(Limited('d'));
+        assert_eq!(
+            queue.commit_atomic(&mut target).unwrap(),
+            Err(RollbackError {
+                error: "s is full",
+                rollback: None
+            })
+        );
+        assert_eq!(target, "a");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.current(), 1);
+        assert!(history.branches.is_empty());
+        history.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "ax");
+
+        let mut queue = history.queue();
+        queue.undo();
+        queue.apply(Limited('b'));
+        queue.apply(Limited('c'));
+        queue.commit_atomic(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abc");
+        assert_eq!(history.len(), 3);
+    }
+
+    #[test]
+    fn commit_atomic_at_limit() {
+        let mut target = String::new();
+        let mut history: History<_> = history::Builder::new().limit(2).build();
+        history.apply(&mut target, Limited('a')).unwrap();
+        history.apply(&mut target, Limited('b')).unwrap();
+
+        let mut queue = history.queue();
+        queue.apply(Limited('c'));
+        queue.apply(Limited('d'));
+        assert_eq!(
+            queue.commit_atomic(&mut target).unwrap(),
+            Err(RollbackError {
+                error: "s is full",
+                rollback: None
+            })
+        );
+        assert_eq!(target, "ab");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.current(), 2);
+        history.undo(&mut target).unwrap().unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "");
+        history.redo(&mut target).unwrap().unwrap();
+        history.redo(&mut target).unwrap().unwrap();
+
+        let mut queue = history.queue();
+        queue.apply(Limited('c'));
+        queue.commit_atomic(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abc");
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.current(), 2);
+        history.undo(&mut target).unwrap().unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "a");
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn try_go_to() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Add('a')).unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Add('c')).unwrap();
+        assert_eq!(
+            history.try_go_to(&mut target, 2, 0),
+            Err(Error::UnknownBranch(2))
+        );
+        assert_eq!(
+            history.try_go_to(&mut target, 0, 3),
+            Err(Error::OutOfRange(3))
+        );
+        history.try_go_to(&mut target, 0, 2).unwrap();
+        assert_eq!(target, "ab");
+        target.clear();
+        assert_eq!(
+            history.try_go_to(&mut target, 0, 0),
+            Err(Error::Action("s is empty", 1))
+        );
+    }
+
+    #[test]
+    fn branch_changed() {
+        use alloc::{boxed::Box, rc::Rc, vec::Vec};
+        use core::cell::RefCell;
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let sink = signals.clone();
+        let mut target = String::new();
+        let mut history = History::new();
+        history.connect(Box::new(move |signal| {
+            if let Signal::BranchChanged { .. } = signal {
+                sink.borrow_mut().push(signal);
+            }
+        }));
+        history.apply(&mut target, Add('a')).unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        history.go_to(&mut target, 0, 1).unwrap().unwrap();
+        assert_eq!(target, "a");
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::BranchChanged { from: 0, to: 1 },
+                Signal::BranchChanged { from: 1, to: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn budget() {
+        struct Image(char, usize);
+
+        impl Action for Image {
+            type Target = String;
+            type Output = ();
+            type Error = &'static str;
+
+            fn apply(&mut self, s: &mut String) -> Result<Image> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<Image> {
+                self.0 = s.pop().ok_or("s is empty")?;
+                Ok(())
+            }
+
+            fn size_hint(&self) -> usize {
+                self.1
+            }
+        }
+
+        let mut target = String::new();
+        let mut history = history::Builder::default().budget(100).build();
+        history.apply(&mut target, Image('a', 30)).unwrap();
+        history.apply(&mut target, Image('b', 30)).unwrap();
+        let ab = history.branch();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Image('c', 80)).unwrap();
+        // Applying `c` creates a new branch and evicts `a`.
+        // The branch with `b` does not count against the budget.
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.memory_usage(), 80);
+        history.go_to(&mut target, ab, 1).unwrap().unwrap();
+        assert_eq!(target, "ab");
+
+        history.apply(&mut target, Image('d', 90)).unwrap();
+        // The branch with `c` was attached to the evicted state.
+        assert_eq!(history.len(), 1);
+        assert!(history.branches.is_empty());
+        assert_eq!(history.memory_usage(), 90);
+        history.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "ab");
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn prune_older_than() {
+        use chrono::{Duration, Utc};
+
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Add('a')).unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        let ab = history.branch();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Add('c')).unwrap();
+        let ac = history.branch();
+        let now = Utc::now();
+        for (i, entry) in history.record.entries.iter_mut().enumerate() {
+            entry.timestamp = now + Duration::hours(i as i64);
+        }
+
+        // The branch with `b` is attached to the state after `a`.
+        assert_eq!(history.prune_older_than(now + Duration::minutes(30)), 1);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.branches[&ab].parent, At::new(ac, 0));
+        history.go_to(&mut target, ab, 1).unwrap().unwrap();
+        assert_eq!(target, "ab");
+        history.go_to(&mut target, ac, 1).unwrap().unwrap();
+        assert_eq!(target, "ac");
+
+        assert_eq!(history.prune_older_than(now + Duration::hours(2)), 1);
+        assert!(history.is_empty());
+        assert!(history.branches.is_empty());
+        assert!(!history.can_undo());
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn zero_retention() {
+        let mut target = String::new();
+        let mut history = history::Builder::default()
+            .retention(chrono::Duration::zero())
+            .build();
+        history.apply(&mut target, Add('a')).unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Add('c')).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.current(), 1);
+        history.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "a");
+    }
 }
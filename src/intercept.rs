@@ -0,0 +1,101 @@
+//! Interceptors that can veto actions.
+//!
+//! Interceptors can be set on the builders of the data structures, where they are
+//! added as a [layer](../layer/trait.Layer.html).
+//! The `Timeline` requires the `alloc` feature to be enabled to use an interceptor.
+
+use crate::layer::{Context, Layer, Next, Op};
+use crate::{Action, Result};
+
+/// Decides if an action is allowed to be applied, undone or redone.
+///
+/// The interceptor is called before the action is executed, with the position of the action
+/// in the data structure. If an error is returned the action is not executed, the state of
+/// the data structure is left unchanged and the error is returned to the caller.
+/// For a group, every action in the group is checked before any of them are executed.
+///
+/// # Examples
+/// ```
+/// # use undo::{intercept::Interceptor, record::Builder, Action};
+/// # include!("../add.rs");
+/// struct Quota(usize);
+///
+/// impl Interceptor<Add> for Quota {
+///     fn apply(&self, _: &Add, at: usize) -> Result<(), &'static str> {
+///         if at < self.0 { Ok(()) } else { Err("quota exceeded") }
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut target = String::new();
+/// let mut record = Builder::default().intercept(Quota(2)).build();
+/// record.apply(&mut target, Add('a')).unwrap();
+/// record.apply(&mut target, Add('b')).unwrap();
+/// assert_eq!(record.apply(&mut target, Add('c')), Err("quota exceeded"));
+/// assert_eq!(target, "ab");
+/// # }
+/// ```
+pub trait Interceptor<A> {
+    /// Called before the action at position `at` is applied.
+    ///
+    /// # Errors
+    /// If an error is returned the action is not applied.
+    fn apply(&self, action: &A, at: usize) -> core::result::Result<(), A::Error>
+    where
+        A: Action,
+    {
+        let _ = (action, at);
+        Ok(())
+    }
+
+    /// Called before the action at position `at` is undone.
+    ///
+    /// # Errors
+    /// If an error is returned the action is not undone.
+    fn undo(&self, action: &A, at: usize) -> core::result::Result<(), A::Error>
+    where
+        A: Action,
+    {
+        let _ = (action, at);
+        Ok(())
+    }
+
+    /// Called before the action at position `at` is redone.
+    ///
+    /// # Errors
+    /// If an error is returned the action is not redone.
+    fn redo(&self, action: &A, at: usize) -> core::result::Result<(), A::Error>
+    where
+        A: Action,
+    {
+        let _ = (action, at);
+        Ok(())
+    }
+}
+
+/// Allows every action.
+impl<A> Interceptor<A> for () {}
+
+/// A layer that calls the interceptor before the actions in an entry.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct Intercept<I>(pub I);
+
+impl<A, I: Interceptor<A>> Layer<A> for Intercept<I> {
+    fn call(&self, _: &Context, action: &mut A, target: &mut A::Target, next: Next<A>) -> Result<A>
+    where
+        A: Action,
+    {
+        next.run(action, target)
+    }
+
+    fn check(&self, cx: &Context, action: &A) -> core::result::Result<(), A::Error>
+    where
+        A: Action,
+    {
+        match cx.op {
+            Op::Apply => self.0.apply(action, cx.position),
+            Op::Undo => self.0.undo(action, cx.position),
+            Op::Redo => self.0.redo(action, cx.position),
+        }
+    }
+}
@@ -0,0 +1,661 @@
+//! A journal that persists the changes made to a record.
+//!
+//! Every change is appended to the journal as it happens, so the record can be rebuilt
+//! after a crash without serializing the whole record on every change.
+//! The journal can be compacted into a snapshot of the target and the record,
+//! so it does not grow without bounds.
+//! The events are written as newline-delimited JSON, and other formats are not supported
+//! since a torn write is detected by the line it was written on.
+
+use crate::merge::{Decision, Never, Policy};
+use crate::{Action, Entry, Record, Signal};
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{fmt, mem};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize, Serialize,
+};
+use std::io::{self, BufRead, Write};
+
+/// A change to the record.
+#[derive(Serialize, Deserialize)]
+enum Event<E, S = ()> {
+    /// The entry was pushed onto the record, or merged into the previous entry as decided
+    /// by `merge`, and then `evicted` entries were evicted from the bottom of the record.
+    Apply {
+        entry: E,
+        merge: Decision,
+        evicted: usize,
+    },
+    Undo,
+    Redo,
+    Saved(bool),
+    /// Only allowed as the first event in the journal.
+    Snapshot(S),
+}
+
+/// The state of the target and the record when the journal was compacted.
+#[derive(Serialize, Deserialize)]
+struct Snapshot<T, E> {
+    target: T,
+    entries: E,
+    current: usize,
+    saved: Option<usize>,
+}
+
+/// A record that appends every change made to it to a journal.
+///
+/// Start the journal with an empty record, or with the record returned by [`replay`].
+///
+/// # Examples
+/// ```
+/// # use undo::{journal::{self, Journal}, Record};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Serialize, Deserialize)]
+/// # struct Add(char);
+/// # impl undo::Action for Add {
+/// #     type Target = String;
+/// #     type Output = ();
+/// #     type Error = &'static str;
+/// #     fn apply(&mut self, s: &mut String) -> undo::Result<Add> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> undo::Result<Add> {
+/// #         self.0 = s.pop().ok_or("s is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// # fn main() {
+/// let mut target = String::new();
+/// let mut journal = Journal::new(Record::new(), Vec::new());
+/// journal.apply(&mut target, Add('a')).unwrap();
+/// journal.apply(&mut target, Add('b')).unwrap();
+/// journal.undo(&mut target).unwrap().unwrap();
+///
+/// let (_, bytes) = journal.into_inner();
+/// let (record, _) = journal::replay(bytes.as_slice(), Record::<Add>::new()).unwrap();
+/// assert_eq!(record.len(), 2);
+/// assert_eq!(record.current(), 1);
+/// # }
+/// ```
+///
+/// [`replay`]: fn.replay.html
+pub struct Journal<A, W, F = Box<dyn FnMut(Signal)>> {
+    record: Record<A, F>,
+    writer: W,
+}
+
+impl<A, W, F> Journal<A, W, F> {
+    /// Returns a journal that appends the changes made to `record` to `writer`.
+    ///
+    /// Groups are not journaled, so the record can not be grouped while the journal owns it.
+    ///
+    /// # Panics
+    /// Panics if `record` has a group that has not been ended.
+    pub fn new(record: Record<A, F>, writer: W) -> Journal<A, W, F> {
+        assert!(!record.is_grouping(), "record has an open group");
+        Journal { record, writer }
+    }
+
+    /// Returns a reference to the record.
+    pub fn record(&self) -> &Record<A, F> {
+        &self.record
+    }
+
+    /// Returns a reference to the writer.
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the record and the writer.
+    pub fn into_inner(self) -> (Record<A, F>, W) {
+        (self.record, self.writer)
+    }
+}
+
+impl<A: Action + Serialize, W: Write, F: FnMut(Signal)> Journal<A, W, F> {
+    /// Pushes the action on top of the record, executes its [`apply`] method
+    /// and appends it to the journal.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned and nothing is written.
+    /// If an error occur when writing to the journal the error is returned,
+    /// but the action is still applied.
+    ///
+    /// [`apply`]: ../trait.Action.html#tymethod.apply
+    pub fn apply(
+        &mut self,
+        target: &mut A::Target,
+        action: A,
+    ) -> Result<A::Output, Error<A::Error>> {
+        let current = self.record.current;
+        let mut entry = Ok(serde_json::Value::Null);
+        let (output, evicted, _) = self
+            .record
+            .__apply_with(target, action, |e| entry = serde_json::to_value(e))
+            .map_err(Error::Action)?;
+        let merge = self.merge_decision(current, evicted);
+        self.append(&Event::Apply {
+            entry: entry?,
+            merge,
+            evicted,
+        })?;
+        Ok(output)
+    }
+
+    /// Returns how the last applied action was merged, from the current position
+    /// of the record before it was applied and the number of evicted entries.
+    fn merge_decision(&self, current: usize, evicted: usize) -> Decision {
+        let now = self.record.current + evicted;
+        if now > current {
+            Decision::No
+        } else if now < current {
+            // Only actions merged by `Action::merge` can annul the previous entry.
+            Decision::Action
+        } else if self.record.entries.back().is_some_and(Entry::is_group) {
+            // Entries created from groups are only extended by `Decision::Group`.
+            Decision::Group
+        } else {
+            Decision::Action
+        }
+    }
+
+    /// Calls [`undo`] on the record and appends it to the journal.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] the error is returned and nothing is written.
+    /// If an error occur when writing to the journal the error is returned,
+    /// but the action is still undone.
+    ///
+    /// [`undo`]: ../record/struct.Record.html#method.undo
+    pub fn undo(&mut self, target: &mut A::Target) -> Option<Result<A::Output, Error<A::Error>>> {
+        Some(match self.record.undo(target)? {
+            Ok(output) => self.append(&Event::Undo).map(|()| output),
+            Err(error) => Err(Error::Action(error)),
+        })
+    }
+
+    /// Calls [`redo`] on the record and appends it to the journal.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`redo`] the error is returned and nothing is written.
+    /// If an error occur when writing to the journal the error is returned,
+    /// but the action is still redone.
+    ///
+    /// [`redo`]: ../record/struct.Record.html#method.redo
+    pub fn redo(&mut self, target: &mut A::Target) -> Option<Result<A::Output, Error<A::Error>>> {
+        Some(match self.record.redo(target)? {
+            Ok(output) => self.append(&Event::Redo).map(|()| output),
+            Err(error) => Err(Error::Action(error)),
+        })
+    }
+
+    /// Marks the target as currently being in a saved or unsaved state
+    /// and appends it to the journal.
+    ///
+    /// # Errors
+    /// If an error occur when writing to the journal the error is returned.
+    pub fn set_saved(&mut self, saved: bool) -> Result<(), Error<A::Error>> {
+        self.record.set_saved(saved);
+        self.append(&Event::Saved(saved))
+    }
+
+    /// Compacts the journal by writing a snapshot of the target and the record to `writer`.
+    ///
+    /// The journal continues in `writer`, and the previous writer is returned
+    /// so it can be removed. Use [`load`] to restore the target and the record.
+    ///
+    /// # Errors
+    /// If an error occur when writing the snapshot the error is returned,
+    /// and the journal continues in the previous writer.
+    ///
+    /// [`load`]: fn.load.html
+    pub fn compact(&mut self, target: &A::Target, writer: W) -> Result<W, Error<A::Error>>
+    where
+        A::Target: Serialize,
+    {
+        let snapshot = Snapshot {
+            target,
+            entries: &self.record.entries,
+            current: self.record.current,
+            saved: self.record.saved,
+        };
+        let event = serde_json::to_vec(&Event::<&Entry<A>, _>::Snapshot(snapshot))?;
+        let writer = core::mem::replace(&mut self.writer, writer);
+        if let Err(error) = self.write(event) {
+            self.writer = writer;
+            return Err(error);
+        }
+        Ok(writer)
+    }
+
+    fn append(&mut self, event: &Event<serde_json::Value>) -> Result<(), Error<A::Error>> {
+        let event = serde_json::to_vec(event)?;
+        self.write(event)
+    }
+
+    /// Writes the event as a single line so a torn write can be detected.
+    fn write(&mut self, mut event: Vec<u8>) -> Result<(), Error<A::Error>> {
+        event.push(b'\n');
+        self.writer.write_all(&event)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<A: fmt::Debug, W: fmt::Debug, F> fmt::Debug for Journal<A, W, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Journal")
+            .field("record", &self.record)
+            .field("writer", &self.writer)
+            .finish()
+    }
+}
+
+/// Rebuilds the record by replaying the events in the journal on `record`.
+///
+/// The record should be built the same way as the record the journal was started with.
+/// No actions are executed, and no signals are emitted while replaying.
+/// The merges and evictions are replayed as they were written to the journal,
+/// so the merge policy, limit, budget and retention period of `record` are not used until
+/// the journal has been replayed.
+/// If the journal has been compacted the record is restored from the snapshot,
+/// but the target is ignored.
+///
+/// A torn write at the end of the journal, for example after a crash, is ignored.
+/// Returns the record and the length in bytes of the valid part of the journal.
+/// The journal should be truncated to this length before more events are appended to it.
+///
+/// # Errors
+/// Returns an error if the journal can not be read, or if it contains an event
+/// that can not be decoded or replayed before the end of the journal.
+#[allow(clippy::type_complexity)]
+pub fn replay<A: Action + DeserializeOwned, F: FnMut(Signal)>(
+    reader: impl BufRead,
+    record: Record<A, F>,
+) -> Result<(Record<A, F>, u64), Error<A::Error>> {
+    read::<A, F, IgnoredAny>(reader, record, |_| None).map(|(_, record, valid)| (record, valid))
+}
+
+/// Restores the target and the record from a journal that has been [compacted].
+///
+/// The record should be built the same way as the record the journal was started with.
+/// The events written after the snapshot are executed on the target, without emitting signals
+/// and without calling the layers of `record`. Like in [`replay`], the merges and evictions
+/// are replayed as they were written to the journal.
+/// Returns the target, the record and the length in bytes of the valid part of the journal.
+///
+/// # Errors
+/// Returns an error if the journal does not start with a snapshot, if it can not be read,
+/// if an action returns an error, or if it contains an event that can not be decoded
+/// or replayed before the end of the journal.
+///
+/// [compacted]: struct.Journal.html#method.compact
+/// [`replay`]: fn.replay.html
+#[allow(clippy::type_complexity)]
+pub fn load<A, F>(
+    reader: impl BufRead,
+    record: Record<A, F>,
+) -> Result<(A::Target, Record<A, F>, u64), Error<A::Error>>
+where
+    A: Action + DeserializeOwned,
+    A::Target: DeserializeOwned,
+    F: FnMut(Signal),
+{
+    match read(reader, record, |target| Some(target))? {
+        (Some(target), record, valid) => Ok((target, record, valid)),
+        (None, _, _) => Err(Error::Corrupted(0)),
+    }
+}
+
+/// Reads the journal, `execute` decides if the events after a snapshot are executed on its target.
+#[allow(clippy::type_complexity)]
+fn read<A: Action + DeserializeOwned, F: FnMut(Signal), T: DeserializeOwned>(
+    mut reader: impl BufRead,
+    mut record: Record<A, F>,
+    execute: fn(&mut T) -> Option<&mut A::Target>,
+) -> Result<(Option<T>, Record<A, F>, u64), Error<A::Error>> {
+    let muted = record.slot.mute(true);
+    // The actions were already allowed by the layers, and the merges and evictions are in the journal.
+    let guard = mem::take(&mut record.guard);
+    let policy = mem::replace(&mut record.policy, Policy::new(Never));
+    let eviction = record.suspend_eviction();
+    let mut target = None;
+    let mut valid = 0;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let n = reader.read_until(b'\n', &mut line)?;
+        // A line that does not end with a newline is a torn write.
+        if n == 0 || line.last() != Some(&b'\n') {
+            break;
+        }
+        let event =
+            match serde_json::from_slice::<Event<Entry<A>, Snapshot<T, VecDeque<Entry<A>>>>>(&line)
+            {
+                Ok(event) => event,
+                // A corrupted last line is also a torn write.
+                Err(_) if reader.fill_buf()?.is_empty() => break,
+                // A torn write before the end means the journal was changed after it was written.
+                Err(error) if error.is_eof() => return Err(Error::Corrupted(valid)),
+                Err(error) => return Err(Error::Format(error)),
+            };
+        match (event, target.as_mut().and_then(execute)) {
+            (
+                Event::Apply {
+                    mut entry,
+                    merge,
+                    evicted,
+                },
+                target,
+            ) => {
+                // The events after a snapshot are executed on the target of the snapshot.
+                if let Some(target) = target {
+                    entry
+                        .apply(target, &record.guard, record.current)
+                        .map_err(Error::Action)?;
+                }
+                record.policy = Policy::new(merge);
+                record.push(entry).map_err(Error::Action)?;
+                if record.current < evicted {
+                    return Err(Error::Corrupted(valid));
+                }
+                (0..evicted).for_each(|_| record.evict());
+            }
+            (Event::Undo, Some(target)) if record.can_undo() => {
+                if let Some(Err(error)) = record.undo(target) {
+                    return Err(Error::Action(error));
+                }
+            }
+            (Event::Redo, Some(target)) if record.can_redo() => {
+                if let Some(Err(error)) = record.redo(target) {
+                    return Err(Error::Action(error));
+                }
+            }
+            (Event::Undo, None) if record.can_undo() => record.current -= 1,
+            (Event::Redo, None) if record.can_redo() => record.current += 1,
+            (Event::Saved(saved), _) => record.set_saved(saved),
+            (Event::Snapshot(snapshot), _)
+                if valid == 0 && snapshot.current <= snapshot.entries.len() =>
+            {
+                target = Some(snapshot.target);
+                record.entries = snapshot.entries;
+                record.current = snapshot.current;
+                record.saved = snapshot.saved;
+            }
+            _ => return Err(Error::Corrupted(valid)),
+        }
+        valid += n as u64;
+    }
+    record.guard = guard;
+    record.policy = policy;
+    record.resume_eviction(eviction);
+    record.slot.mute(muted);
+    Ok((target, record, valid))
+}
+
+/// The error returned by the journal.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// The action returned an error.
+    Action(E),
+    /// An error occurred when reading or writing the journal.
+    Io(io::Error),
+    /// An error occurred when encoding or decoding an event.
+    Format(serde_json::Error),
+    /// The event at the byte offset is torn or can not be replayed on the record,
+    /// or the journal does not start with a snapshot when it is loaded.
+    Corrupted(u64),
+}
+
+impl<E> From<io::Error> for Error<E> {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl<E> From<serde_json::Error> for Error<E> {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Format(error)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Action(error) => write!(f, "action failed: {error}"),
+            Error::Io(error) => write!(f, "journal io failed: {error}"),
+            Error::Format(error) => write!(f, "journal format is invalid: {error}"),
+            Error::Corrupted(offset) => write!(f, "journal is corrupted at byte {offset}"),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Action(error) => Some(error),
+            Error::Io(error) => Some(error),
+            Error::Format(error) => Some(error),
+            Error::Corrupted(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::journal::{self, Journal};
+    use crate::*;
+    use alloc::{string::String, vec::Vec};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Add(char);
+
+    impl Action for Add {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Add> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Add> {
+            self.0 = s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+    }
+
+    fn journal() -> (String, Vec<u8>) {
+        let mut target = String::new();
+        let mut journal = Journal::new(Record::new(), Vec::new());
+        journal.apply(&mut target, Add('a')).unwrap();
+        journal.apply(&mut target, Add('b')).unwrap();
+        journal.set_saved(true).unwrap();
+        journal.apply(&mut target, Add('c')).unwrap();
+        journal.undo(&mut target).unwrap().unwrap();
+        journal.undo(&mut target).unwrap().unwrap();
+        journal.redo(&mut target).unwrap().unwrap();
+        let (_, bytes) = journal.into_inner();
+        (target, bytes)
+    }
+
+    #[test]
+    fn replay() {
+        let (target, bytes) = journal();
+        let (mut record, len) = journal::replay(bytes.as_slice(), Record::<Add>::new()).unwrap();
+        assert_eq!(len, bytes.len() as u64);
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.current(), 2);
+        assert!(record.is_saved());
+        let mut replayed = target.clone();
+        record.redo(&mut replayed).unwrap().unwrap();
+        assert_eq!(replayed, "abc");
+        record.go_to(&mut replayed, 0).unwrap().unwrap();
+        assert_eq!(replayed, "");
+    }
+
+    #[test]
+    fn replay_merges() {
+        struct Policy;
+
+        impl merge::MergePolicy for Policy {
+            fn decide(&self, last: &merge::Info, _: &merge::Info) -> merge::Decision {
+                match last.saved {
+                    true => merge::Decision::No,
+                    false => merge::Decision::Group,
+                }
+            }
+        }
+
+        let build = || -> Record<Add> { record::Builder::new().merge_policy(Policy).build() };
+        let mut target = String::new();
+        let mut journal = Journal::new(build(), Vec::new());
+        journal.apply(&mut target, Add('a')).unwrap();
+        journal.apply(&mut target, Add('b')).unwrap();
+        journal.set_saved(true).unwrap();
+        journal.apply(&mut target, Add('c')).unwrap();
+        let (original, bytes) = journal.into_inner();
+        assert_eq!(original.len(), 2);
+
+        // The merges are replayed as they happened, whatever the policy of the record.
+        let (mut record, _) = journal::replay(bytes.as_slice(), Record::<Add>::new()).unwrap();
+        assert_eq!(record.len(), 2);
+        record.undo(&mut target).unwrap().unwrap();
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "");
+    }
+
+    #[test]
+    #[should_panic(expected = "record has an open group")]
+    fn open_group() {
+        let mut record = Record::<Add>::new();
+        record.begin_group(None);
+        Journal::new(record, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn torn_write() {
+        let (_, mut bytes) = journal();
+        let complete = bytes.len() as u64;
+        bytes.extend_from_slice(br#"{"Apply":{"action":"#);
+        let (record, len) = journal::replay(bytes.as_slice(), Record::<Add>::new()).unwrap();
+        assert_eq!(len, complete);
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.current(), 2);
+
+        bytes.truncate(complete as usize - 1);
+        let (record, len) = journal::replay(bytes.as_slice(), Record::<Add>::new()).unwrap();
+        assert!(len < complete);
+        assert_eq!(record.current(), 1);
+    }
+
+    #[test]
+    fn corrupted() {
+        let (_, mut bytes) = journal();
+        bytes.splice(0..0, b"garbage\n".iter().copied());
+        assert!(matches!(
+            journal::replay(bytes.as_slice(), Record::<Add>::new()),
+            Err(journal::Error::Format(_))
+        ));
+        let (_, mut bytes) = journal();
+        let second = bytes.iter().position(|&b| b == b'\n').unwrap() + 1;
+        let end = second + bytes[second..].iter().position(|&b| b == b'\n').unwrap();
+        bytes.drain(end - 4..end);
+        assert!(matches!(
+            journal::replay(bytes.as_slice(), Record::<Add>::new()),
+            Err(journal::Error::Corrupted(offset)) if offset == second as u64
+        ));
+        let undo = b"\"Undo\"\n";
+        assert!(matches!(
+            journal::replay(&undo[..], Record::<Add>::new()),
+            Err(journal::Error::Corrupted(0))
+        ));
+    }
+
+    #[test]
+    fn compact() {
+        let mut target = String::new();
+        let mut journal = Journal::new(Record::new(), Vec::new());
+        journal.apply(&mut target, Add('a')).unwrap();
+        journal.apply(&mut target, Add('b')).unwrap();
+        journal.set_saved(true).unwrap();
+        journal.undo(&mut target).unwrap().unwrap();
+        let old = journal.compact(&target, Vec::new()).unwrap();
+        assert!(!old.is_empty());
+        journal.redo(&mut target).unwrap().unwrap();
+        journal.apply(&mut target, Add('c')).unwrap();
+        let (_, bytes) = journal.into_inner();
+
+        let (mut loaded, mut record, len) =
+            journal::load(bytes.as_slice(), Record::<Add>::new()).unwrap();
+        assert_eq!(len, bytes.len() as u64);
+        assert_eq!(loaded, "abc");
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.current(), 3);
+        record.undo(&mut loaded).unwrap().unwrap();
+        assert!(record.is_saved());
+        record.go_to(&mut loaded, 0).unwrap().unwrap();
+        assert_eq!(loaded, "");
+
+        let (record, _) = journal::replay(bytes.as_slice(), Record::<Add>::new()).unwrap();
+        assert_eq!(record.len(), 3);
+        assert_eq!(record.current(), 3);
+
+        assert!(matches!(
+            journal::load(old.as_slice(), Record::<Add>::new()),
+            Err(journal::Error::Corrupted(0))
+        ));
+    }
+
+    #[test]
+    fn load_with_settings() {
+        use crate::intercept::Interceptor;
+        use alloc::sync::Arc;
+        use core::{
+            mem,
+            sync::atomic::{AtomicBool, Ordering},
+        };
+
+        struct Lock(Arc<AtomicBool>);
+
+        impl Interceptor<Add> for Lock {
+            fn apply(&self, _: &Add, _: usize) -> Result<Add> {
+                match self.0.load(Ordering::Relaxed) {
+                    true => Err("locked"),
+                    false => Ok(()),
+                }
+            }
+        }
+
+        let locked = Arc::new(AtomicBool::new(false));
+        let build = || -> Record<Add> {
+            record::Builder::default()
+                .budget(2 * mem::size_of::<Add>())
+                .intercept(Lock(locked.clone()))
+                .build()
+        };
+        let mut target = String::new();
+        let mut journal = Journal::new(build(), Vec::new());
+        journal.compact(&target, Vec::new()).unwrap();
+        journal.apply(&mut target, Add('a')).unwrap();
+        journal.apply(&mut target, Add('b')).unwrap();
+        journal.apply(&mut target, Add('c')).unwrap();
+        journal.undo(&mut target).unwrap().unwrap();
+        let (original, bytes) = journal.into_inner();
+        assert_eq!(original.len(), 2);
+
+        locked.store(true, Ordering::Relaxed);
+        let (mut loaded, mut record, _) = journal::load(bytes.as_slice(), build()).unwrap();
+        assert_eq!(loaded, target);
+        assert_eq!(record.len(), original.len());
+        assert_eq!(record.current(), original.current());
+        assert_eq!(record.budget(), original.budget());
+        record.undo(&mut loaded).unwrap().unwrap();
+        assert_eq!(loaded, "a");
+        assert!(!record.can_undo());
+    }
+}
@@ -0,0 +1,436 @@
+//! Middleware that wraps the calls made to the actions.
+//!
+//! Layers can be set on the builders of the data structures.
+//! The `Timeline` requires the `alloc` feature to be enabled to use a layer.
+
+use crate::{Action, Result};
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+#[cfg(any(feature = "alloc", feature = "tracing"))]
+use core::fmt;
+#[cfg(not(feature = "alloc"))]
+use core::marker::PhantomData;
+
+/// Wraps the calls made to [`apply`], [`undo`] and [`redo`].
+///
+/// The layer decides if and how the call continues by calling [`Next::run`].
+/// Layers are composed into a [`Stack`], where the first layer added to the
+/// builder is the outermost one.
+///
+/// # Examples
+/// ```
+/// # use undo::{layer::{Context, Layer, Next}, record::Builder, Action};
+/// # include!("../add.rs");
+/// struct Log;
+///
+/// impl Layer<Add> for Log {
+///     fn call(
+///         &self,
+///         cx: &Context,
+///         action: &mut Add,
+///         target: &mut String,
+///         next: Next<Add>,
+///     ) -> undo::Result<Add> {
+///         println!("{:?} at {}", cx.op, cx.position);
+///         next.run(action, target)
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut target = String::new();
+/// let mut record = Builder::default().layer(Log).build();
+/// record.apply(&mut target, Add('a')).unwrap();
+/// record.undo(&mut target).unwrap().unwrap();
+/// # }
+/// ```
+///
+/// [`apply`]: ../trait.Action.html#tymethod.apply
+/// [`undo`]: ../trait.Action.html#tymethod.undo
+/// [`redo`]: ../trait.Action.html#method.redo
+/// [`Next::run`]: struct.Next.html#method.run
+/// [`Stack`]: struct.Stack.html
+pub trait Layer<A> {
+    /// Wraps the call described by `cx` to the action.
+    ///
+    /// # Errors
+    /// The error returned by the call, or an error from the layer itself.
+    fn call(
+        &self,
+        cx: &Context,
+        action: &mut A,
+        target: &mut A::Target,
+        next: Next<A>,
+    ) -> Result<A>
+    where
+        A: Action;
+
+    /// Checks if the call described by `cx` is allowed before any action in the entry is called.
+    ///
+    /// Every action in the entry is checked before the first one is called,
+    /// so a rejected action in a group does not leave the target partially changed.
+    ///
+    /// # Errors
+    /// If an error is returned none of the actions in the entry are called.
+    fn check(&self, cx: &Context, action: &A) -> core::result::Result<(), A::Error>
+    where
+        A: Action,
+    {
+        let _ = (cx, action);
+        Ok(())
+    }
+}
+
+/// Passes every call through unchanged.
+impl<A> Layer<A> for () {
+    fn call(&self, _: &Context, action: &mut A, target: &mut A::Target, next: Next<A>) -> Result<A>
+    where
+        A: Action,
+    {
+        next.run(action, target)
+    }
+}
+
+/// The method that is called on the action.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Op {
+    /// [`Action::apply`](../trait.Action.html#tymethod.apply) is called.
+    Apply,
+    /// [`Action::undo`](../trait.Action.html#tymethod.undo) is called.
+    Undo,
+    /// [`Action::redo`](../trait.Action.html#method.redo) is called.
+    Redo,
+}
+
+/// Information about the entry whose action is called.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Context {
+    /// The method that is called on the action.
+    pub op: Op,
+    /// The position the entry has in the data structure.
+    pub position: usize,
+    /// When the entry was created.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    #[cfg(feature = "chrono")]
+    pub timestamp: DateTime<Utc>,
+}
+
+/// The rest of the layers and the call to the action.
+pub struct Next<'a, A: Action>(&'a mut dyn FnMut(&mut A, &mut A::Target) -> Result<A>);
+
+impl<A: Action> Next<'_, A> {
+    /// Continues the call with the next layer, or calls the action if it is the last layer.
+    ///
+    /// # Errors
+    /// The error returned by the call.
+    pub fn run(self, action: &mut A, target: &mut A::Target) -> Result<A> {
+        (self.0)(action, target)
+    }
+}
+
+/// Two layers where `Outer` wraps `Inner`.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    /// Returns a stack where `outer` wraps `inner`.
+    pub fn new(inner: Inner, outer: Outer) -> Stack<Inner, Outer> {
+        Stack { inner, outer }
+    }
+}
+
+impl<A, Inner: Layer<A>, Outer: Layer<A>> Layer<A> for Stack<Inner, Outer> {
+    fn call(&self, cx: &Context, action: &mut A, target: &mut A::Target, next: Next<A>) -> Result<A>
+    where
+        A: Action,
+    {
+        let mut inner = |action: &mut A, target: &mut A::Target| {
+            self.inner.call(cx, action, target, Next(&mut *next.0))
+        };
+        self.outer.call(cx, action, target, Next(&mut inner))
+    }
+
+    fn check(&self, cx: &Context, action: &A) -> core::result::Result<(), A::Error>
+    where
+        A: Action,
+    {
+        self.outer.check(cx, action)?;
+        self.inner.check(cx, action)
+    }
+}
+
+/// A layer that emits a `tracing` event with the [`Display`] of the action for every call.
+///
+/// The data structures emit an event for every call without this layer,
+/// but it can not include the action since actions are not required to implement [`Display`].
+///
+/// Requires the `tracing` feature to be enabled.
+///
+/// [`Display`]: https://doc.rust-lang.org/core/fmt/trait.Display.html
+#[cfg(feature = "tracing")]
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct Trace;
+
+#[cfg(feature = "tracing")]
+impl<A: fmt::Display> Layer<A> for Trace {
+    fn call(&self, cx: &Context, action: &mut A, target: &mut A::Target, next: Next<A>) -> Result<A>
+    where
+        A: Action,
+    {
+        tracing::debug!(op = ?cx.op, position = cx.position, action = %action, "call");
+        next.run(action, target)
+    }
+}
+
+/// Shared handle to the layer used by the data structures.
+#[cfg(feature = "alloc")]
+pub(crate) struct Guard<A>(Arc<dyn Layer<A> + Send + Sync>);
+
+/// Calls the actions directly since layers require the `alloc` feature.
+#[cfg(not(feature = "alloc"))]
+pub(crate) struct Guard<A>(PhantomData<fn(A)>);
+
+impl<A> Guard<A> {
+    #[cfg(feature = "alloc")]
+    pub fn new(layer: impl Layer<A> + Send + Sync + 'static) -> Guard<A> {
+        Guard(Arc::new(layer))
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    pub fn new(_: impl Layer<A>) -> Guard<A> {
+        Guard(PhantomData)
+    }
+}
+
+impl<A: Action> Guard<A> {
+    /// Calls the action through the layer.
+    #[cfg_attr(not(feature = "alloc"), allow(unused_mut))]
+    pub fn call(&self, cx: &Context, action: &mut A, target: &mut A::Target) -> Result<A> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(op = ?cx.op, position = cx.position, "call");
+        let mut call = |action: &mut A, target: &mut A::Target| match cx.op {
+            Op::Apply => action.apply(target),
+            Op::Undo => action.undo(target),
+            Op::Redo => action.redo(target),
+        };
+        #[cfg(feature = "alloc")]
+        return self.0.call(cx, action, target, Next(&mut call));
+        #[cfg(not(feature = "alloc"))]
+        call(action, target)
+    }
+
+    /// Checks the call with the layer.
+    pub fn check(&self, cx: &Context, action: &A) -> core::result::Result<(), A::Error> {
+        #[cfg(feature = "alloc")]
+        return self.0.check(cx, action);
+        #[cfg(not(feature = "alloc"))]
+        {
+            let _ = (cx, action);
+            Ok(())
+        }
+    }
+}
+
+impl<A> Clone for Guard<A> {
+    fn clone(&self) -> Self {
+        #[cfg(feature = "alloc")]
+        return Guard(self.0.clone());
+        #[cfg(not(feature = "alloc"))]
+        Guard(PhantomData)
+    }
+}
+
+impl<A> Default for Guard<A> {
+    fn default() -> Self {
+        Guard::new(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A> fmt::Debug for Guard<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Guard { .. }")
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::layer::{Context, Layer, Next, Op};
+    use crate::*;
+    use alloc::{string::String, sync::Arc, vec::Vec};
+    use core::fmt;
+    use std::sync::Mutex;
+
+    struct Add(char);
+
+    impl Action for Add {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Add> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Add> {
+            self.0 = s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Add {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "Add({})", self.0)
+        }
+    }
+
+    type Log = Arc<Mutex<Vec<(&'static str, Op, usize)>>>;
+
+    struct Trace(&'static str, Log);
+
+    impl Layer<Add> for Trace {
+        fn call(
+            &self,
+            cx: &Context,
+            action: &mut Add,
+            target: &mut String,
+            next: Next<Add>,
+        ) -> Result<Add> {
+            self.1.lock().unwrap().push((self.0, cx.op, cx.position));
+            next.run(action, target)
+        }
+    }
+
+    struct Upper;
+
+    impl Layer<Add> for Upper {
+        fn call(
+            &self,
+            _: &Context,
+            action: &mut Add,
+            target: &mut String,
+            next: Next<Add>,
+        ) -> Result<Add> {
+            action.0 = action.0.to_ascii_uppercase();
+            next.run(action, target)
+        }
+    }
+
+    #[test]
+    fn stack() {
+        let log = Log::default();
+        let mut target = String::new();
+        let mut record = record::Builder::default()
+            .layer(Trace("outer", log.clone()))
+            .layer(Trace("inner", log.clone()))
+            .layer(Upper)
+            .build();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.apply(&mut target, Add('b')).unwrap();
+        record.undo(&mut target).unwrap().unwrap();
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "AB");
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                ("outer", Op::Apply, 0),
+                ("inner", Op::Apply, 0),
+                ("outer", Op::Apply, 1),
+                ("inner", Op::Apply, 1),
+                ("outer", Op::Undo, 1),
+                ("inner", Op::Undo, 1),
+                ("outer", Op::Redo, 1),
+                ("inner", Op::Redo, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn history_branches() {
+        let log = Log::default();
+        let mut target = String::new();
+        let mut history = history::Builder::default()
+            .layer(Trace("layer", log.clone()))
+            .build();
+        history.apply(&mut target, Add('a')).unwrap();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        log.lock().unwrap().clear();
+        history.go_to(&mut target, 0, 1).unwrap().unwrap();
+        assert_eq!(target, "a");
+        assert_eq!(
+            *log.lock().unwrap(),
+            [("layer", Op::Undo, 0), ("layer", Op::Redo, 0)]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn trace() {
+        use alloc::{format, string::ToString};
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        #[derive(Default)]
+        struct Events(Mutex<Vec<String>>);
+
+        struct Message<'a>(&'a mut String);
+
+        impl Visit for Message<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0.push_str(&format!(" {}={:?}", field.name(), value));
+            }
+        }
+
+        impl Subscriber for Events {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &span::Attributes) -> span::Id {
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event) {
+                let mut message = event.metadata().name().to_string();
+                event.record(&mut Message(&mut message));
+                self.0.lock().unwrap().push(message);
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let events = Arc::new(Events::default());
+        tracing::subscriber::with_default(events.clone(), || {
+            let mut target = String::new();
+            let mut record = record::Builder::default()
+                .limit(1)
+                .layer(layer::Trace)
+                .build();
+            record.apply(&mut target, Add('a')).unwrap();
+            record.apply(&mut target, Add('b')).unwrap();
+        });
+        let events = events.0.lock().unwrap();
+        assert_eq!(events.len(), 5);
+        assert!(events[0].ends_with("message=call op=Apply position=0"));
+        assert!(events[1].ends_with("message=call op=Apply position=0 action=Add(a)"));
+        assert!(events[2].ends_with("message=call op=Apply position=1"));
+        assert!(events[3].ends_with("message=call op=Apply position=1 action=Add(b)"));
+        assert!(events[4].ends_with("message=evicted limit=1"));
+    }
+}
@@ -11,12 +11,17 @@
 //! * [Record](record/struct.Record.html) provides basic undo-redo functionality.
 //! * [Timeline](timeline/struct.Timeline.html) provides basic undo-redo functionality using a fixed size.
 //! * [History](history/struct.History.html) provides non-linear undo-redo functionality that allows you to jump between different branches.
+//! * [CollaborativeRecord](collaborative/struct.CollaborativeRecord.html) provides undo-redo functionality for multiple authors editing the same target.
 //! * A queues that wraps a record or history and extends them with queue functionality.
 //! * A checkpoints that wraps a record or history and extends them with checkpoint functionality.
 //! * Actions can be merged into a single action by implementing the
 //!   [merge](trait.Action.html#method.merge) method on the action.
 //!   This allows smaller actions to be used to build more complex operations, or smaller incremental changes to be
 //!   merged into larger changes that can be undone and redone in a single step.
+//!   When actions are allowed to merge can be configured with a [merge policy](merge/trait.MergePolicy.html).
+//! * Actions can be vetoed before they are applied, undone or redone by an [interceptor](intercept/trait.Interceptor.html).
+//! * The calls made to the actions can be wrapped by [layers](layer/trait.Layer.html), for example to add logging or timing.
+//! * Any number of actions can be grouped together so they are undone and redone in a single step.
 //! * The target can be marked as being saved to disk and the data-structures can track the saved state and notify
 //!   when it changes.
 //! * The amount of changes being tracked can be configured by the user so only the `N` most recent changes are stored.
@@ -26,9 +31,17 @@
 //! # Cargo Feature Flags
 //!
 //! * `alloc`: Enables the use of the alloc crate, enabled by default.
+//! * `std`: Implements `std::error::Error` for the error types and enables delivering signals through channels, implies `alloc`.
+//! * `futures`: Enables delivering signals through a stream, implies `std`.
 //! * `colored`: Enables colored output when visualizing the display structures, enabled by default.
 //! * `chrono`: Enables time stamps and time travel.
-//! * `serde`: Enables serialization and deserialization.
+//! * `serde`: Enables serialization and deserialization, and a [versioned format](persist/index.html) for storing the data structures.
+//! * `file`: Enables a record [storage](storage/struct.FileStorage.html) that pages entries out to a file, implies `std` and `serde`.
+//! * `json-journal`: Enables persisting the changes made to a record in a [journal](journal/index.html) of newline-delimited JSON, implies `std` and `serde`.
+//! * `postcard`: Enables encoding a timeline into a fixed size buffer without `alloc`, implies `serde`.
+//! * `rkyv`: Enables zero-copy [archived](archive/index.html) records and histories that can be inspected without deserializing them, implies `alloc`.
+//! * `tracing`: Emits [tracing](https://docs.rs/tracing) spans and events for the operations on the data structures.
+//!   Add the [`Trace`](layer/struct.Trace.html) layer to include the `Display` of the actions in the events.
 //!
 //! # Examples
 //!
@@ -83,28 +96,59 @@ pub struct ReadmeDocTest;
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 #[cfg(feature = "alloc")]
 mod any;
+#[cfg(feature = "rkyv")]
+pub mod archive;
+#[cfg(feature = "std")]
+pub mod channel;
+#[cfg(feature = "alloc")]
+pub mod collaborative;
+#[cfg(any(feature = "serde", feature = "rkyv"))]
+mod convert;
 #[cfg(feature = "alloc")]
 mod format;
 #[cfg(feature = "alloc")]
 pub mod history;
+pub mod intercept;
+#[cfg(feature = "json-journal")]
+pub mod journal;
+pub mod layer;
+#[cfg(feature = "alloc")]
+pub mod merge;
+#[cfg(all(feature = "alloc", feature = "serde"))]
+pub mod persist;
 #[cfg(feature = "alloc")]
 pub mod record;
+#[cfg(feature = "alloc")]
+pub mod storage;
 pub mod timeline;
 
 #[cfg(feature = "alloc")]
 use crate::format::Format;
+use crate::layer::{Context, Guard, Op};
+#[cfg(feature = "alloc")]
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
 #[cfg(feature = "chrono")]
 use chrono::{DateTime, Utc};
-use core::fmt;
+use core::{fmt, mem};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-pub use self::timeline::Timeline;
 #[cfg(feature = "alloc")]
-pub use self::{any::AnyAction, history::History, record::Record};
+pub use self::{
+    any::AnyAction, collaborative::CollaborativeRecord, history::History, merge::MergePolicy,
+    record::Record,
+};
+pub use self::{intercept::Interceptor, layer::Layer, timeline::Timeline};
 
 /// A specialized Result type for undo-redo operations.
 pub type Result<A> = core::result::Result<<A as Action>::Output, <A as Action>::Error>;
@@ -138,17 +182,67 @@ pub trait Action {
     ///
     /// You should return:
     /// * `Yes` if you have merged the two commands.
-    /// The `other` command will not be added to the stack.
+    ///   The `other` command will not be added to the stack.
     /// * `No` if you have not merged the two commands.
-    /// The `other` command will be added to the stack.
+    ///   The `other` command will be added to the stack.
     /// * `Annul` if the two commands cancels each other out.
-    /// This will removed both `self` and `other` from the stack.
+    ///   This will removed both `self` and `other` from the stack.
     fn merge(&mut self, other: Self) -> Merged<Self>
     where
         Self: Sized,
     {
         Merged::No(other)
     }
+
+    /// Returns the key used by the [`SameKey`](merge/struct.SameKey.html) merge policy.
+    ///
+    /// Actions are only merged by the policy if both actions return the same key.
+    /// The default implementation returns `None`.
+    fn merge_key(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns `true` if the action commutes with `other`.
+    ///
+    /// Two actions commute if applying them in any order gives the same result.
+    /// This is used by selective undo to move an action past the actions applied after it.
+    /// The default implementation returns `false`.
+    fn commutes_with(&self, other: &Self) -> bool
+    where
+        Self: Sized,
+    {
+        let _ = other;
+        false
+    }
+
+    /// Returns the approximate number of bytes used by the action.
+    ///
+    /// This is used to keep the memory used by a record within its
+    /// [`budget`](record/struct.Builder.html#method.budget).
+    /// The default implementation returns the size of the action itself,
+    /// which does not include any memory it owns on the heap.
+    fn size_hint(&self) -> usize {
+        mem::size_of_val(self)
+    }
+}
+
+/// Operational transformation of actions.
+///
+/// Used to reorder actions so that an action can be undone without undoing
+/// the actions made after it, for example by other users, and to apply actions
+/// that were made against an older state of the target.
+pub trait Transform: Action {
+    /// Includes the effect of `other` in the action.
+    ///
+    /// Both actions have been made against the same state of the target.
+    /// Afterwards the action can be applied after `other` has been applied.
+    fn include(&mut self, other: &Self);
+
+    /// Excludes the effect of `other` from the action.
+    ///
+    /// The action has been made after `other` was applied.
+    /// Afterwards the action can be applied as if `other` was never applied.
+    fn exclude(&mut self, other: &Self);
 }
 
 /// Says if the action have been merged with another action.
@@ -169,6 +263,84 @@ pub enum Merged<A> {
     Annul,
 }
 
+/// The error returned when undoing an action that is not at the top of the stack.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum UndoAtError<E> {
+    /// The action does not commute with the action at the index.
+    Conflict(usize),
+    /// The branch starts after the action, and would no longer be reachable if it was undone.
+    Branch(usize),
+    /// The action returned an error when it was undone.
+    Action(E),
+}
+
+/// The error returned when an atomic go to fails.
+///
+/// The data structure tries to roll back to the position it started from,
+/// and any error that occurs while doing so is also returned.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct RollbackError<E> {
+    /// The error that caused the rollback.
+    pub error: E,
+    /// The error that occurred during the rollback, if any.
+    ///
+    /// If this is set the target is left somewhere between the start and the destination.
+    pub rollback: Option<E>,
+}
+
+/// The error returned by the `try_*` methods on the data structures.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub enum Error<E> {
+    /// There are no actions that can be undone.
+    NothingToUndo,
+    /// There are no actions that can be redone.
+    NothingToRedo,
+    /// The position is larger than the number of actions.
+    OutOfRange(usize),
+    /// The branch does not exist.
+    UnknownBranch(usize),
+    /// The action returned an error at the position.
+    Action(E, usize),
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NothingToUndo => f.write_str("nothing to undo"),
+            Error::NothingToRedo => f.write_str("nothing to redo"),
+            Error::OutOfRange(position) => write!(f, "position {position} is out of range"),
+            Error::UnknownBranch(branch) => write!(f, "branch {branch} does not exist"),
+            Error::Action(error, position) => {
+                write!(f, "action failed at position {position}: {error}")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Action(error, _) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the index of the action that failed when walking to `to`,
+/// where `at` is the position the walk stopped at.
+fn failed_at(at: usize, to: usize) -> usize {
+    // The action before the position is undone when walking backwards.
+    if to < at {
+        at - 1
+    } else {
+        at
+    }
+}
+
 /// A position in a history tree.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
@@ -189,6 +361,12 @@ impl At {
 ///
 /// For example, if the record can no longer redo any actions, it sends a `Redo(false)`
 /// signal to tell the user.
+///
+/// The `Undo`, `Redo` and `Saved` signals are the coarse signals that only report
+/// when the state changes, while the rest report what happened to the entries.
+/// Use [`coarse`](fn.coarse.html) to only receive the coarse signals.
+/// The indices are the positions of the entries in the data structure,
+/// where `0` is the oldest entry.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum Signal {
@@ -198,6 +376,92 @@ pub enum Signal {
     Redo(bool),
     /// Says if the target is in a saved state.
     Saved(bool),
+    /// An action was applied and added as the entry at the index.
+    Applied(usize),
+    /// The entry at the index was undone.
+    Undone(usize),
+    /// The entry at the index was redone.
+    Redone(usize),
+    /// An action was applied and merged into the last entry.
+    Merged,
+    /// An action was applied and annulled the last entry, which was removed.
+    Annulled,
+    /// The oldest entry was removed because the limit was reached.
+    Evicted,
+    /// All the entries were removed.
+    Cleared,
+    /// The current branch changed.
+    BranchChanged {
+        /// The previous branch.
+        from: usize,
+        /// The new branch.
+        to: usize,
+    },
+}
+
+impl Signal {
+    /// Returns `true` if the signal is one of the coarse `Undo`, `Redo` or `Saved` signals.
+    pub fn is_coarse(&self) -> bool {
+        matches!(self, Signal::Undo(_) | Signal::Redo(_) | Signal::Saved(_))
+    }
+}
+
+/// Wraps the slot so it only receives the coarse `Undo`, `Redo` and `Saved` signals.
+///
+/// # Examples
+/// ```
+/// # use undo::{Record, Signal};
+/// # include!("../add.rs");
+/// # fn main() {
+/// let mut record: Record<Add> = Record::new();
+/// record.connect(Box::new(undo::coarse(|signal| assert!(signal.is_coarse()))));
+/// record.apply(&mut String::new(), Add('a')).unwrap();
+/// # }
+/// ```
+pub fn coarse(mut f: impl FnMut(Signal)) -> impl FnMut(Signal) {
+    move |signal| {
+        if signal.is_coarse() {
+            f(signal);
+        }
+    }
+}
+
+/// A handle to a listener subscribed to the signals of a data structure.
+///
+/// Requires the `alloc` feature to be enabled.
+#[cfg(feature = "alloc")]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct Subscription(usize);
+
+/// The listeners subscribed to the signals.
+///
+/// Listeners are not cloned, a cloned data structure starts without any listeners.
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+struct Listeners {
+    next: usize,
+    listeners: BTreeMap<usize, Box<dyn FnMut(Signal) + Send + Sync>>,
+}
+
+#[cfg(feature = "alloc")]
+impl Listeners {
+    fn subscribe(&mut self, f: impl FnMut(Signal) + Send + Sync + 'static) -> Subscription {
+        let id = self.next;
+        self.next += 1;
+        self.listeners.insert(id, Box::new(f));
+        Subscription(id)
+    }
+
+    fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        self.listeners.remove(&subscription.0).is_some()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Clone for Listeners {
+    fn clone(&self) -> Self {
+        Listeners::default()
+    }
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -205,13 +469,42 @@ pub enum Signal {
 struct Slot<F> {
     #[cfg_attr(feature = "serde", serde(default = "Option::default", skip))]
     f: Option<F>,
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    listeners: Listeners,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    muted: bool,
+}
+
+impl<F> Slot<F> {
+    /// Mutes or unmutes the slot and returns if it was muted.
+    fn mute(&mut self, muted: bool) -> bool {
+        core::mem::replace(&mut self.muted, muted)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn subscribe(&mut self, f: impl FnMut(Signal) + Send + Sync + 'static) -> Subscription {
+        self.listeners.subscribe(f)
+    }
+
+    #[cfg(feature = "alloc")]
+    fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        self.listeners.unsubscribe(subscription)
+    }
 }
 
 impl<F: FnMut(Signal)> Slot<F> {
     fn emit(&mut self, signal: Signal) {
+        if self.muted {
+            return;
+        }
         if let Some(ref mut f) = self.f {
             f(signal);
         }
+        #[cfg(feature = "alloc")]
+        for f in self.listeners.listeners.values_mut() {
+            f(signal);
+        }
     }
 
     fn emit_if(&mut self, cond: bool, signal: Signal) {
@@ -219,17 +512,35 @@ impl<F: FnMut(Signal)> Slot<F> {
             self.emit(signal);
         }
     }
+
+    /// Emits the signals for the entries that were undone or redone
+    /// when moving from position `from` to position `to`.
+    fn emit_moved(&mut self, from: usize, to: usize) {
+        if to < from {
+            (to..from).rev().for_each(|i| self.emit(Signal::Undone(i)));
+        } else {
+            (from..to).for_each(|i| self.emit(Signal::Redone(i)));
+        }
+    }
 }
 
 impl<F> From<F> for Slot<F> {
     fn from(f: F) -> Slot<F> {
-        Slot { f: Some(f) }
+        Slot {
+            f: Some(f),
+            ..Slot::default()
+        }
     }
 }
 
 impl<F> Default for Slot<F> {
     fn default() -> Self {
-        Slot { f: None }
+        Slot {
+            f: None,
+            #[cfg(feature = "alloc")]
+            listeners: Listeners::default(),
+            muted: false,
+        }
     }
 }
 
@@ -243,26 +554,320 @@ impl<F> fmt::Debug for Slot<F> {
 }
 
 /// Wrapper around an action that contains additional metadata.
+///
+/// The entry is opaque, and is only exposed so it can be held by a
+/// [storage](storage/trait.Storage.html).
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
-struct Entry<A> {
+pub struct Entry<A> {
     action: A,
+    /// The actions that were applied after `action` as part of the same group.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")
+    )]
+    group: Vec<A>,
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "Option::default", skip_serializing_if = "Option::is_none")
+    )]
+    label: Option<String>,
     #[cfg(feature = "chrono")]
     timestamp: DateTime<Utc>,
+    /// When the entry was last modified by a merge.
+    #[cfg(feature = "chrono")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "Option::default", skip_serializing_if = "Option::is_none")
+    )]
+    modified: Option<DateTime<Utc>>,
+}
+
+impl<A> Entry<A> {
+    /// Returns `true` if the entry was created from a group.
+    fn is_group(&self) -> bool {
+        #[cfg(feature = "alloc")]
+        if self.label.is_some() || !self.group.is_empty() {
+            return true;
+        }
+        false
+    }
+
+    /// Returns when the entry was last modified.
+    #[cfg(feature = "chrono")]
+    fn modified(&self) -> DateTime<Utc> {
+        self.modified.unwrap_or(self.timestamp)
+    }
+
+    /// Returns the information passed to the layers.
+    fn context(&self, op: Op, position: usize) -> Context {
+        Context {
+            op,
+            position,
+            #[cfg(feature = "chrono")]
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A: Action> Entry<A> {
+    /// Returns the information used by the merge policy.
+    fn info(&self, position: usize, saved: bool) -> merge::Info {
+        merge::Info {
+            position,
+            saved,
+            key: self.action.merge_key(),
+            #[cfg(feature = "chrono")]
+            timestamp: self.timestamp,
+            #[cfg(feature = "chrono")]
+            modified: self.modified(),
+        }
+    }
+
+    /// Adds the actions of `entry` to the group of this entry.
+    fn append(&mut self, entry: Entry<A>) {
+        self.group.push(entry.action);
+        self.group.extend(entry.group);
+        self.label = self.label.take().or(entry.label);
+        #[cfg(feature = "chrono")]
+        {
+            self.modified = Some(entry.timestamp);
+        }
+    }
+
+    /// Returns the sum of the size hints of the actions in the entry.
+    fn size_hint(&self) -> usize {
+        let group: usize = self.group.iter().map(A::size_hint).sum();
+        self.action.size_hint() + group
+    }
+}
+
+impl<A: Action> Entry<A> {
+    /// Returns `true` if all the actions in the entry commute with all the actions in `other`.
+    fn commutes_with(&self, other: &Entry<A>) -> bool {
+        let commutes = |action: &A| {
+            #[cfg(feature = "alloc")]
+            if !other.group.iter().all(|other| action.commutes_with(other)) {
+                return false;
+            }
+            action.commutes_with(&other.action)
+        };
+        #[cfg(feature = "alloc")]
+        if !self.group.iter().all(commutes) {
+            return false;
+        }
+        commutes(&self.action)
+    }
+
+    /// Calls `apply` on the actions through the layer in `guard`.
+    ///
+    /// If an action in a group fails, the actions that were already applied are undone.
+    #[cfg_attr(not(feature = "alloc"), allow(unused_mut))]
+    fn apply(&mut self, target: &mut A::Target, guard: &Guard<A>, position: usize) -> Result<A> {
+        let cx = self.context(Op::Apply, position);
+        self.check(&cx, guard)?;
+        let mut output = guard.call(&cx, &mut self.action, target)?;
+        #[cfg(feature = "alloc")]
+        for i in 0..self.group.len() {
+            match guard.call(&cx, &mut self.group[i], target) {
+                Ok(o) => output = o,
+                Err(error) => {
+                    self.rollback_forward(target, i);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Calls `undo` on the actions through the layer in `guard`.
+    ///
+    /// If an action in a group fails, the actions that were already undone are redone.
+    fn undo(&mut self, target: &mut A::Target, guard: &Guard<A>, position: usize) -> Result<A> {
+        let cx = self.context(Op::Undo, position);
+        self.check(&cx, guard)?;
+        #[cfg(feature = "alloc")]
+        for i in (0..self.group.len()).rev() {
+            if let Err(error) = guard.call(&cx, &mut self.group[i], target) {
+                self.rollback_backward(target, i + 1);
+                return Err(error);
+            }
+        }
+        guard
+            .call(&cx, &mut self.action, target)
+            .inspect_err(|_| self.rollback_backward(target, 0))
+    }
+
+    /// Calls `redo` on the actions through the layer in `guard`.
+    ///
+    /// If an action in a group fails, the actions that were already redone are undone.
+    #[cfg_attr(not(feature = "alloc"), allow(unused_mut))]
+    fn redo(&mut self, target: &mut A::Target, guard: &Guard<A>, position: usize) -> Result<A> {
+        let cx = self.context(Op::Redo, position);
+        self.check(&cx, guard)?;
+        let mut output = guard.call(&cx, &mut self.action, target)?;
+        #[cfg(feature = "alloc")]
+        for i in 0..self.group.len() {
+            match guard.call(&cx, &mut self.group[i], target) {
+                Ok(o) => output = o,
+                Err(error) => {
+                    self.rollback_forward(target, i);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    /// Checks all the actions in the entry with the layer in `guard`,
+    /// in the order they are called.
+    fn check(&self, cx: &Context, guard: &Guard<A>) -> core::result::Result<(), A::Error> {
+        #[cfg(feature = "alloc")]
+        if cx.op == Op::Undo {
+            for action in self.group.iter().rev() {
+                guard.check(cx, action)?;
+            }
+            return guard.check(cx, &self.action);
+        }
+        guard.check(cx, &self.action)?;
+        #[cfg(feature = "alloc")]
+        for action in &self.group {
+            guard.check(cx, action)?;
+        }
+        Ok(())
+    }
+
+    /// Undoes the first action and the first `len` actions in the group,
+    /// after they were applied or redone.
+    ///
+    /// The actions are called directly, since the layers already allowed them.
+    #[cfg(feature = "alloc")]
+    fn rollback_forward(&mut self, target: &mut A::Target, len: usize) {
+        for action in self.group[..len].iter_mut().rev() {
+            let _ = action.undo(target);
+        }
+        let _ = self.action.undo(target);
+    }
+
+    /// Redoes the actions in the group from `start`, after they were undone.
+    #[cfg(feature = "alloc")]
+    fn rollback_backward(&mut self, target: &mut A::Target, start: usize) {
+        for action in &mut self.group[start..] {
+            let _ = action.redo(target);
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn rollback_backward(&mut self, _: &mut A::Target, _: usize) {}
+
+    #[allow(clippy::needless_update)]
+    fn merge(&mut self, entry: Entry<A>) -> Merged<Entry<A>> {
+        // Groups are never merged.
+        if self.is_group() || entry.is_group() {
+            return Merged::No(entry);
+        }
+        match self.action.merge(entry.action) {
+            Merged::Yes => {
+                #[cfg(feature = "chrono")]
+                {
+                    self.modified = Some(entry.timestamp);
+                }
+                Merged::Yes
+            }
+            Merged::No(action) => Merged::No(Entry { action, ..entry }),
+            Merged::Annul => Merged::Annul,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A: ToString> Entry<A> {
+    fn text(&self) -> String {
+        match self.label {
+            Some(ref label) => label.clone(),
+            None => self.action.to_string(),
+        }
+    }
 }
 
 impl<A> From<A> for Entry<A> {
     fn from(action: A) -> Self {
         Entry {
             action,
+            #[cfg(feature = "alloc")]
+            group: Vec::new(),
+            #[cfg(feature = "alloc")]
+            label: None,
             #[cfg(feature = "chrono")]
             timestamp: Utc::now(),
+            #[cfg(feature = "chrono")]
+            modified: None,
         }
     }
 }
 
 impl<A: fmt::Display> fmt::Display for Entry<A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature = "alloc")]
+        if let Some(ref label) = self.label {
+            return f.write_str(label);
+        }
         (&self.action as &dyn fmt::Display).fmt(f)
     }
 }
+
+/// A group of actions that has been applied but not yet added as an entry.
+#[cfg(feature = "alloc")]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+struct Group<A> {
+    label: Option<String>,
+    actions: Vec<A>,
+}
+
+#[cfg(feature = "alloc")]
+impl<A> Group<A> {
+    fn new(label: Option<String>) -> Group<A> {
+        Group {
+            label,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Converts the group into an entry, returns `None` if the group is empty.
+    fn into_entry(self) -> Option<Entry<A>> {
+        let mut actions = self.actions.into_iter();
+        let action = actions.next()?;
+        let mut entry = Entry::from(action);
+        entry.group = actions.collect();
+        entry.label = self.label;
+        Some(entry)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A: Action> Group<A> {
+    fn push(&mut self, action: A) {
+        let merged = match self.actions.last_mut() {
+            Some(last) => last.merge(action),
+            None => Merged::No(action),
+        };
+        match merged {
+            Merged::Yes => (),
+            Merged::Annul => {
+                self.actions.pop();
+            }
+            Merged::No(action) => self.actions.push(action),
+        }
+    }
+
+    /// Folds a nested group into this group.
+    fn extend(&mut self, group: Group<A>) {
+        for action in group.actions {
+            self.push(action);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Policies for when and how actions are merged.
+//!
+//! A policy decides if two entries are merged, and if they are merged by
+//! [`Action::merge`](../trait.Action.html#method.merge) or combined into a single entry.
+
+use alloc::sync::Arc;
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Duration, Utc};
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Decides if and how two entries are merged.
+///
+/// # Examples
+/// ```
+/// # use undo::{merge::{Decision, Info, MergePolicy}, record::Builder, Record};
+/// # include!("../add.rs");
+/// // Combines the entries into one until the target is saved,
+/// // even though `Add` does not implement `merge`.
+/// struct UntilSaved;
+///
+/// impl MergePolicy for UntilSaved {
+///     fn decide(&self, last: &Info, _: &Info) -> Decision {
+///         if last.saved {
+///             Decision::No
+///         } else {
+///             Decision::Group
+///         }
+///     }
+/// }
+///
+/// # fn main() {
+/// let mut target = String::new();
+/// let mut record: Record<Add> = Builder::new().merge_policy(UntilSaved).build();
+/// record.apply(&mut target, Add('a')).unwrap();
+/// record.set_saved(true);
+/// record.apply(&mut target, Add('b')).unwrap();
+/// record.apply(&mut target, Add('c')).unwrap();
+/// assert_eq!(record.len(), 2);
+///
+/// record.undo(&mut target).unwrap().unwrap();
+/// assert_eq!(target, "a");
+/// # }
+/// ```
+pub trait MergePolicy {
+    /// Returns how `next` is merged into `last`.
+    fn decide(&self, last: &Info, next: &Info) -> Decision;
+}
+
+/// How two entries are merged.
+///
+/// A decision is also a policy that always makes that decision.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub enum Decision {
+    /// The entries are not merged.
+    #[default]
+    No,
+    /// The actions are merged by [`Action::merge`](../trait.Action.html#method.merge),
+    /// which can still decline the merge or annul the entries.
+    /// Entries created from groups are never merged this way.
+    Action,
+    /// The actions of `next` are added to the group of `last`, so the entries are undone
+    /// and redone together like a group. This also works for actions that do not implement
+    /// [`Action::merge`](../trait.Action.html#method.merge).
+    Group,
+}
+
+impl MergePolicy for Decision {
+    fn decide(&self, _: &Info, _: &Info) -> Decision {
+        *self
+    }
+}
+
+/// Information about an entry that is considered for merging.
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Info {
+    /// The index of the entry in the record, where `0` is the oldest entry.
+    pub position: usize,
+    /// Says if the target is in a saved state at the entry.
+    pub saved: bool,
+    /// The [`merge_key`](../trait.Action.html#method.merge_key) of the action.
+    pub key: Option<u64>,
+    /// When the entry was created.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    #[cfg(feature = "chrono")]
+    pub timestamp: DateTime<Utc>,
+    /// When the entry was last modified by a merge.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    #[cfg(feature = "chrono")]
+    pub modified: DateTime<Utc>,
+}
+
+/// Never merges any entries.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct Never;
+
+impl MergePolicy for Never {
+    fn decide(&self, _: &Info, _: &Info) -> Decision {
+        Decision::No
+    }
+}
+
+/// Always merges the actions by `Action::merge`, even if the target is in a saved state.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct Always;
+
+impl MergePolicy for Always {
+    fn decide(&self, _: &Info, _: &Info) -> Decision {
+        Decision::Action
+    }
+}
+
+/// Merges the actions by `Action::merge` unless the target is in a saved state.
+///
+/// This is the default policy.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct Unsaved;
+
+impl MergePolicy for Unsaved {
+    fn decide(&self, last: &Info, _: &Info) -> Decision {
+        action_if(!last.saved)
+    }
+}
+
+/// Merges the entries if both actions have the same merge key,
+/// unless the target is in a saved state.
+#[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct SameKey;
+
+impl MergePolicy for SameKey {
+    fn decide(&self, last: &Info, next: &Info) -> Decision {
+        action_if(!last.saved && last.key.is_some() && last.key == next.key)
+    }
+}
+
+/// Merges the entries if the next entry was created within the interval of
+/// when the last entry was modified, unless the target is in a saved state.
+///
+/// Requires the `chrono` feature to be enabled.
+#[cfg(feature = "chrono")]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct TimeWindow {
+    interval: Duration,
+}
+
+#[cfg(feature = "chrono")]
+impl TimeWindow {
+    /// Returns a time window policy with the provided interval.
+    pub fn new(interval: Duration) -> TimeWindow {
+        TimeWindow { interval }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl MergePolicy for TimeWindow {
+    fn decide(&self, last: &Info, next: &Info) -> Decision {
+        action_if(!last.saved && next.timestamp - last.modified < self.interval)
+    }
+}
+
+/// Returns [`Decision::Action`] if `merge` is `true`, and [`Decision::No`] otherwise.
+fn action_if(merge: bool) -> Decision {
+    if merge {
+        Decision::Action
+    } else {
+        Decision::No
+    }
+}
+
+/// Shared handle to the merge policy used by the data structures.
+#[derive(Clone)]
+pub(crate) struct Policy(Arc<dyn MergePolicy + Send + Sync>);
+
+impl Policy {
+    pub fn new(policy: impl MergePolicy + Send + Sync + 'static) -> Policy {
+        Policy(Arc::new(policy))
+    }
+
+    pub fn decide(&self, last: &Info, next: &Info) -> Decision {
+        self.0.decide(last, next)
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::new(Unsaved)
+    }
+}
+
+impl fmt::Debug for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Policy { .. }")
+    }
+}
@@ -0,0 +1,517 @@
+//! A stable, versioned serialization format for the data structures.
+//!
+//! The serde implementations on the data structures follow their internal layout,
+//! which can change between versions of the library. The functions in this module
+//! instead use the data types defined here, wrapped in a document that is tagged
+//! with the [`VERSION`] of the format.
+//!
+//! Documents stored with another version of the format are passed to a [`Migrate`] hook
+//! that converts them into the current format. The hook for `()` rejects them.
+//!
+//! Only the entries, the positions and the limit are stored. The slot, the merge policy,
+//! the layers, the budget, the retention period and any groups that have not been ended
+//! are not stored.
+//!
+//! Requires the `serde` feature to be enabled.
+//!
+//! # Examples
+//! ```
+//! # use undo::{persist, Record};
+//! # #[derive(serde::Serialize, serde::Deserialize)]
+//! # struct Add(char);
+//! # impl undo::Action for Add {
+//! #     type Target = String;
+//! #     type Output = ();
+//! #     type Error = &'static str;
+//! #     fn apply(&mut self, s: &mut String) -> undo::Result<Add> {
+//! #         s.push(self.0);
+//! #         Ok(())
+//! #     }
+//! #     fn undo(&mut self, s: &mut String) -> undo::Result<Add> {
+//! #         self.0 = s.pop().ok_or("s is empty")?;
+//! #         Ok(())
+//! #     }
+//! # }
+//! # fn main() {
+//! let mut target = String::new();
+//! let mut record = Record::new();
+//! record.apply(&mut target, Add('a')).unwrap();
+//! record.apply(&mut target, Add('b')).unwrap();
+//!
+//! let mut bytes = Vec::new();
+//! let mut serializer = serde_json::Serializer::new(&mut bytes);
+//! persist::serialize_record(&record, &mut serializer).unwrap();
+//!
+//! let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+//! let record: Record<Add> = persist::deserialize_record(&mut deserializer).unwrap();
+//! assert_eq!(record.len(), 2);
+//! # }
+//! ```
+//!
+//! [`VERSION`]: constant.VERSION.html
+//! [`Migrate`]: trait.Migrate.html
+
+use crate::convert::{history_data, into_history, into_record, record_data};
+use crate::{History, Record};
+use core::{fmt, marker::PhantomData};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub use crate::convert::{BranchData, EntryData, HistoryData, RecordData};
+
+/// The current version of the format.
+pub const VERSION: u32 = 1;
+
+/// Converts documents stored with another version of the format into the current format.
+///
+/// # Examples
+/// ```
+/// # use serde::{Deserialize, Deserializer};
+/// # use undo::persist::{self, EntryData, Migrate, RecordData};
+/// # use undo::Record;
+/// # #[derive(serde::Serialize, serde::Deserialize)]
+/// # struct Add(char);
+/// # impl undo::Action for Add {
+/// #     type Target = String;
+/// #     type Output = ();
+/// #     type Error = &'static str;
+/// #     fn apply(&mut self, s: &mut String) -> undo::Result<Add> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> undo::Result<Add> {
+/// #         self.0 = s.pop().ok_or("s is empty")?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// /// Version 0 stored the actions as a list of characters.
+/// struct FromChars;
+///
+/// impl Migrate<RecordData<Add>> for FromChars {
+///     fn migrate<'de, D: Deserializer<'de>>(
+///         &self,
+///         version: u32,
+///         data: D,
+///     ) -> Result<RecordData<Add>, D::Error> {
+///         assert_eq!(version, 0);
+///         let chars = Vec::<char>::deserialize(data)?;
+///         Ok(RecordData {
+///             current: chars.len(),
+///             limit: usize::MAX,
+///             saved: None,
+///             entries: chars
+///                 .into_iter()
+///                 .map(|c| EntryData {
+///                     action: Add(c),
+///                     group: Vec::new(),
+///                     label: None,
+///                     timestamp: None,
+///                     modified: None,
+///                 })
+///                 .collect(),
+///         })
+///     }
+/// }
+///
+/// # fn main() {
+/// let json = r#"{"version":0,"data":["a","b"]}"#;
+/// let mut deserializer = serde_json::Deserializer::from_str(json);
+/// let record: Record<Add> =
+///     persist::deserialize_record_with(&mut deserializer, &FromChars).unwrap();
+/// assert_eq!(record.len(), 2);
+/// # }
+/// ```
+pub trait Migrate<T> {
+    /// Deserializes `data` that was stored with `version` of the format
+    /// and converts it into the current format.
+    ///
+    /// # Errors
+    /// Returns an error if the data can not be deserialized or converted.
+    fn migrate<'de, D: Deserializer<'de>>(
+        &self,
+        version: u32,
+        data: D,
+    ) -> core::result::Result<T, D::Error>;
+}
+
+/// Rejects every other version of the format.
+impl<T> Migrate<T> for () {
+    fn migrate<'de, D: Deserializer<'de>>(
+        &self,
+        version: u32,
+        _: D,
+    ) -> core::result::Result<T, D::Error> {
+        Err(de::Error::custom(format_args!(
+            "unsupported version {version}, expected version {VERSION}"
+        )))
+    }
+}
+
+/// Serializes the record in the current version of the format.
+///
+/// # Errors
+/// Returns an error if the serializer fails.
+pub fn serialize_record<A: Serialize, F, S: Serializer>(
+    record: &Record<A, F>,
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error> {
+    Document {
+        version: VERSION,
+        data: record_data(record),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a record stored in the current version of the format.
+///
+/// # Errors
+/// Returns an error if the document can not be deserialized,
+/// was stored with another version of the format, or is invalid.
+pub fn deserialize_record<'de, A: Deserialize<'de>, F, D: Deserializer<'de>>(
+    deserializer: D,
+) -> core::result::Result<Record<A, F>, D::Error> {
+    deserialize_record_with(deserializer, &())
+}
+
+/// Deserializes a record, using `migrate` if it was stored with another version of the format.
+///
+/// # Errors
+/// Returns an error if the document can not be deserialized or migrated, or is invalid.
+pub fn deserialize_record_with<'de, A, F, D>(
+    deserializer: D,
+    migrate: &impl Migrate<RecordData<A>>,
+) -> core::result::Result<Record<A, F>, D::Error>
+where
+    A: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let data = DocumentSeed::new(migrate).deserialize(deserializer)?;
+    into_record(data).map_err(de::Error::custom)
+}
+
+/// Serializes the history in the current version of the format.
+///
+/// # Errors
+/// Returns an error if the serializer fails.
+pub fn serialize_history<A: Serialize, F, S: Serializer>(
+    history: &History<A, F>,
+    serializer: S,
+) -> core::result::Result<S::Ok, S::Error> {
+    Document {
+        version: VERSION,
+        data: history_data(history),
+    }
+    .serialize(serializer)
+}
+
+/// Deserializes a history stored in the current version of the format.
+///
+/// # Errors
+/// Returns an error if the document can not be deserialized,
+/// was stored with another version of the format, or is invalid.
+pub fn deserialize_history<'de, A: Deserialize<'de>, F, D: Deserializer<'de>>(
+    deserializer: D,
+) -> core::result::Result<History<A, F>, D::Error> {
+    deserialize_history_with(deserializer, &())
+}
+
+/// Deserializes a history, using `migrate` if it was stored with another version of the format.
+///
+/// # Errors
+/// Returns an error if the document can not be deserialized or migrated, or is invalid.
+pub fn deserialize_history_with<'de, A, F, D>(
+    deserializer: D,
+    migrate: &impl Migrate<HistoryData<A>>,
+) -> core::result::Result<History<A, F>, D::Error>
+where
+    A: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let data = DocumentSeed::new(migrate).deserialize(deserializer)?;
+    into_history(data).map_err(de::Error::custom)
+}
+
+/// The document that wraps the data with the version of the format.
+#[derive(Serialize)]
+struct Document<T> {
+    version: u32,
+    data: T,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Version,
+    Data,
+}
+
+/// Deserializes the document, migrating the data if it has another version.
+struct DocumentSeed<'a, T, M> {
+    migrate: &'a M,
+    data: PhantomData<fn() -> T>,
+}
+
+impl<'a, T, M> DocumentSeed<'a, T, M> {
+    fn new(migrate: &'a M) -> Self {
+        DocumentSeed {
+            migrate,
+            data: PhantomData,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>, M: Migrate<T>> DeserializeSeed<'de> for DocumentSeed<'_, T, M> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<T, D::Error> {
+        deserializer.deserialize_struct("Document", &["version", "data"], self)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, M: Migrate<T>> Visitor<'de> for DocumentSeed<'_, T, M> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a versioned document")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> core::result::Result<T, S::Error> {
+        let version = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let seed = DataSeed {
+            version,
+            migrate: self.migrate,
+            data: PhantomData,
+        };
+        seq.next_element_seed(seed)?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))
+    }
+
+    fn visit_map<V: MapAccess<'de>>(self, mut map: V) -> core::result::Result<T, V::Error> {
+        // The version has to be known before the data can be deserialized.
+        let version = match map.next_key()? {
+            Some(Field::Version) => map.next_value()?,
+            Some(Field::Data) => {
+                return Err(de::Error::custom("`version` must come before `data`"))
+            }
+            None => return Err(de::Error::missing_field("version")),
+        };
+        let seed = DataSeed {
+            version,
+            migrate: self.migrate,
+            data: PhantomData,
+        };
+        match map.next_key()? {
+            Some(Field::Data) => map.next_value_seed(seed),
+            Some(Field::Version) => Err(de::Error::duplicate_field("version")),
+            None => Err(de::Error::missing_field("data")),
+        }
+    }
+}
+
+/// Deserializes the data of the document with the given version.
+struct DataSeed<'a, T, M> {
+    version: u32,
+    migrate: &'a M,
+    data: PhantomData<fn() -> T>,
+}
+
+impl<'de, T: Deserialize<'de>, M: Migrate<T>> DeserializeSeed<'de> for DataSeed<'_, T, M> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> core::result::Result<T, D::Error> {
+        if self.version == VERSION {
+            T::deserialize(deserializer)
+        } else {
+            self.migrate.migrate(self.version, deserializer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::persist::{self, HistoryData, Migrate};
+    use crate::*;
+    use alloc::{string::String, vec::Vec};
+    use serde::{Deserialize, Deserializer, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Add(char);
+
+    impl Action for Add {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Add> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Add> {
+            self.0 = s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+    }
+
+    fn to_json(history: &History<Add>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        persist::serialize_history(history, &mut serde_json::Serializer::new(&mut bytes)).unwrap();
+        bytes
+    }
+
+    fn from_json(json: &[u8]) -> serde_json::Result<History<Add>> {
+        persist::deserialize_history(&mut serde_json::Deserializer::from_slice(json))
+    }
+
+    #[test]
+    fn record() {
+        let mut target = String::new();
+        let mut record = record::Builder::default().limit(10).build();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.begin_group(Some(String::from("bc")));
+        record.apply(&mut target, Add('b')).unwrap();
+        record.apply(&mut target, Add('c')).unwrap();
+        record.end_group();
+        record.set_saved(true);
+        record.undo(&mut target).unwrap().unwrap();
+
+        let mut bytes = Vec::new();
+        persist::serialize_record(&record, &mut serde_json::Serializer::new(&mut bytes)).unwrap();
+        let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+        let mut record: Record<Add> = persist::deserialize_record(&mut deserializer).unwrap();
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 1);
+        assert_eq!(record.limit(), 10);
+        assert!(!record.is_saved());
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abc");
+        assert!(record.is_saved());
+    }
+
+    #[test]
+    fn history() {
+        let mut target = String::new();
+        let mut history = History::new();
+        history.apply(&mut target, Add('a')).unwrap();
+        history.apply(&mut target, Add('b')).unwrap();
+        let ab = history.branch();
+        history.undo(&mut target).unwrap().unwrap();
+        history.apply(&mut target, Add('c')).unwrap();
+        let ac = history.branch();
+
+        let mut history = from_json(&to_json(&history)).unwrap();
+        assert_eq!(history.branch(), ac);
+        history.go_to(&mut target, ab, 2).unwrap().unwrap();
+        assert_eq!(target, "ab");
+        history.go_to(&mut target, ac, 2).unwrap().unwrap();
+        assert_eq!(target, "ac");
+    }
+
+    #[test]
+    fn invalid() {
+        fn data() -> HistoryData<Add> {
+            let mut target = String::new();
+            let mut history = History::new();
+            history.apply(&mut target, Add('a')).unwrap();
+            history.apply(&mut target, Add('b')).unwrap();
+            history.undo(&mut target).unwrap().unwrap();
+            history.apply(&mut target, Add('c')).unwrap();
+            let mut json: serde_json::Value = serde_json::from_slice(&to_json(&history)).unwrap();
+            serde_json::from_value(json["data"].take()).unwrap()
+        }
+
+        fn load(data: HistoryData<Add>) -> serde_json::Result<History<Add>> {
+            let document = persist::Document { version: 1, data };
+            from_json(&serde_json::to_vec(&document).unwrap())
+        }
+
+        fn error(data: HistoryData<Add>) -> String {
+            load(data).unwrap_err().to_string()
+        }
+
+        assert!(load(data()).is_ok());
+
+        let mut d = data();
+        d.record.limit = 1;
+        assert!(error(d).contains("more entries than the limit"));
+
+        let mut d = data();
+        d.branches[0].parent.0 = 9;
+        assert!(error(d).contains("does not exist"));
+
+        let mut d = data();
+        d.branches[0].parent.1 = 3;
+        assert!(error(d).contains("position 3 is out of range"));
+
+        let mut d = data();
+        d.next = 3;
+        let mut branch = d.branches[0].clone();
+        branch.parent = (branch.id, 1);
+        d.branches[0].parent = (2, 1);
+        branch.id = 2;
+        d.branches.push(branch);
+        assert!(error(d).contains("does not lead to the current branch"));
+
+        let mut d = data();
+        d.branches.push(d.branches[0].clone());
+        assert!(error(d).contains("is not unique"));
+
+        let mut d = data();
+        d.record.saved = Some(1);
+        d.saved = Some((d.branches[0].id, 1));
+        assert!(error(d).contains("saved in more than one position"));
+
+        let mut d = data();
+        d.record.saved = None;
+        d.saved = Some((d.branch, 3));
+        assert!(error(d).contains("position 3 is out of range"));
+    }
+
+    #[test]
+    fn version() {
+        let history = History::<Add>::new();
+        let json = String::from_utf8(to_json(&history)).unwrap();
+        assert!(json.starts_with(r#"{"version":1,"data":"#));
+
+        let newer = json.replacen("\"version\":1", "\"version\":2", 1);
+        let error = from_json(newer.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("unsupported version 2"));
+
+        let swapped = r#"{"data":null,"version":1}"#;
+        assert!(from_json(swapped.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn migrate() {
+        struct Upgrade;
+
+        impl Migrate<HistoryData<Add>> for Upgrade {
+            fn migrate<'de, D: Deserializer<'de>>(
+                &self,
+                version: u32,
+                data: D,
+            ) -> core::result::Result<HistoryData<Add>, D::Error> {
+                assert_eq!(version, 2);
+                let mut data = HistoryData::<Add>::deserialize(data)?;
+                data.record.limit = 1;
+                Ok(data)
+            }
+        }
+
+        let history = History::<Add>::new();
+        let json = String::from_utf8(to_json(&history)).unwrap();
+        let newer = json.replacen("\"version\":1", "\"version\":2", 1);
+        let mut deserializer = serde_json::Deserializer::from_str(&newer);
+        let history: History<Add> =
+            persist::deserialize_history_with(&mut deserializer, &Upgrade).unwrap();
+        assert_eq!(history.limit(), 1);
+    }
+}
@@ -1,6 +1,13 @@
 //! A record of actions.
 
-use crate::{Action, At, Entry, Format, History, Merged, Result, Signal, Slot};
+use crate::intercept::Intercept;
+use crate::layer::{Guard, Layer, Stack};
+use crate::merge::{Decision, MergePolicy, Never, Policy};
+use crate::storage::{IntoActionError, Storage};
+use crate::{
+    failed_at, Action, At, Entry, Error, Format, Group, History, Merged, Result, RollbackError,
+    Signal, Slot, Subscription, Transform, UndoAtError,
+};
 use alloc::{
     boxed::Box,
     collections::VecDeque,
@@ -9,13 +16,15 @@ use alloc::{
 };
 use core::{
     fmt::{self, Write},
+    iter, mem,
     num::NonZeroUsize,
 };
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "chrono")]
 use {
-    chrono::{DateTime, Utc},
+    crate::merge::TimeWindow,
+    chrono::{DateTime, Duration, Utc},
     core::cmp::Ordering,
     core::convert::identity,
 };
@@ -28,6 +37,10 @@ use {
 /// The user can give the record a function that is called each time the state
 /// changes by using the [`builder`](struct.RecordBuilder.html).
 ///
+/// The serde implementations only store the entries, the positions, the limit and any
+/// open groups. The slot, the merge policy, the layers, the budget and the retention period
+/// are skipped, and are left as the defaults when the record is deserialized.
+///
 /// # Examples
 /// ```
 /// # use undo::Record;
@@ -52,15 +65,40 @@ use {
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
-    serde(bound(serialize = "A: Serialize", deserialize = "A: Deserialize<'de>"))
+    serde(bound(
+        serialize = "A: Serialize, S: Serialize",
+        deserialize = "A: Deserialize<'de>, S: Deserialize<'de>"
+    ))
 )]
 #[derive(Clone)]
-pub struct Record<A, F = Box<dyn FnMut(Signal)>> {
-    pub(crate) entries: VecDeque<Entry<A>>,
-    current: usize,
+pub struct Record<A, F = Box<dyn FnMut(Signal)>, S = VecDeque<Entry<A>>> {
+    pub(crate) entries: S,
+    pub(crate) current: usize,
     limit: NonZeroUsize,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    budget: Option<usize>,
+    #[cfg(feature = "chrono")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    retention: Option<Duration>,
     pub(crate) saved: Option<usize>,
     pub(crate) slot: Slot<F>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")
+    )]
+    groups: Vec<Group<A>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) policy: Policy,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) guard: Guard<A>,
+}
+
+/// The eviction settings of a record while they are suspended.
+pub(crate) struct Eviction {
+    limit: NonZeroUsize,
+    budget: Option<usize>,
+    #[cfg(feature = "chrono")]
+    retention: Option<Duration>,
 }
 
 impl<A> Record<A> {
@@ -89,6 +127,23 @@ impl<A, F> Record<A, F> {
         self.entries.shrink_to_fit();
     }
 
+    /// Returns a queue.
+    pub fn queue(&mut self) -> Queue<'_, A, F> {
+        Queue::from(self)
+    }
+
+    /// Returns a checkpoint.
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, A, F> {
+        Checkpoint::from(self)
+    }
+
+    /// Returns a structure for configurable formatting of the record.
+    pub fn display(&self) -> Display<'_, A, F> {
+        Display::from(self)
+    }
+}
+
+impl<A, F, S: Storage<A>> Record<A, F, S> {
     /// Returns the number of actions in the record.
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -104,6 +159,19 @@ impl<A, F> Record<A, F> {
         self.limit.get()
     }
 
+    /// Returns the memory budget of the record in bytes, if any.
+    pub fn budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Returns how long the actions are kept in the record, if there is a limit.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    #[cfg(feature = "chrono")]
+    pub fn retention(&self) -> Option<Duration> {
+        self.retention
+    }
+
     /// Sets how the signal should be handled when the state changes.
     ///
     /// The previous slot is returned if it exists.
@@ -116,6 +184,23 @@ impl<A, F> Record<A, F> {
         self.slot.f.take()
     }
 
+    /// Subscribes a listener that is called with every signal, in addition to the slot.
+    ///
+    /// The listener must be `Send` and `Sync` so the data structure can still be shared between threads.
+    /// The returned handle can be used to [`unsubscribe`] the listener.
+    ///
+    /// [`unsubscribe`]: struct.Record.html#method.unsubscribe
+    pub fn subscribe(&mut self, f: impl FnMut(Signal) + Send + Sync + 'static) -> Subscription {
+        self.slot.subscribe(f)
+    }
+
+    /// Unsubscribes the listener.
+    ///
+    /// Returns `true` if the listener was subscribed.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        self.slot.unsubscribe(subscription)
+    }
+
     /// Returns `true` if the record can undo.
     pub fn can_undo(&self) -> bool {
         self.current() > 0
@@ -128,7 +213,7 @@ impl<A, F> Record<A, F> {
 
     /// Returns `true` if the target is in a saved state, `false` otherwise.
     pub fn is_saved(&self) -> bool {
-        self.saved.map_or(false, |saved| saved == self.current())
+        self.saved.is_some_and(|saved| saved == self.current())
     }
 
     /// Returns the position of the current action.
@@ -136,23 +221,37 @@ impl<A, F> Record<A, F> {
         self.current
     }
 
-    /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<A, F> {
-        Queue::from(self)
-    }
-
-    /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<A, F> {
-        Checkpoint::from(self)
+    /// Returns `true` if a group has been begun but not yet ended.
+    pub fn is_grouping(&self) -> bool {
+        !self.groups.is_empty()
     }
 
-    /// Returns a structure for configurable formatting of the record.
-    pub fn display(&self) -> Display<A, F> {
-        Display::from(self)
+    /// Begins a group of actions.
+    ///
+    /// Actions applied while the group is open are applied to the target at once,
+    /// but are only added to the record when the outermost group is ended.
+    /// The group is then undone and redone in a single step, and its `label`,
+    /// if any, is used as its text.
+    ///
+    /// Groups can be nested, in which case the inner groups are folded into the outer group.
+    /// If an action fails when the group is undone or redone, the actions in the group
+    /// that already ran are rolled back before the error is returned.
+    ///
+    /// The methods that move in the record, like [`undo`], [`redo`] and [`go_to`],
+    /// end all open groups before moving, which adds the outermost group as an entry.
+    ///
+    /// [`undo`]: struct.Record.html#method.undo
+    /// [`redo`]: struct.Record.html#method.redo
+    /// [`go_to`]: struct.Record.html#method.go_to
+    pub fn begin_group(&mut self, label: Option<String>) {
+        self.groups.push(Group::new(label));
     }
 }
 
-impl<A: Action, F: FnMut(Signal)> Record<A, F> {
+impl<A: Action, F: FnMut(Signal), S: Storage<A>> Record<A, F, S>
+where
+    S::Error: IntoActionError<A::Error>,
+{
     /// Pushes the action on top of the record and executes its [`apply`] method.
     ///
     /// # Errors
@@ -167,62 +266,271 @@ impl<A: Action, F: FnMut(Signal)> Record<A, F> {
     pub(crate) fn __apply(
         &mut self,
         target: &mut A::Target,
-        mut action: A,
-    ) -> core::result::Result<(A::Output, bool, VecDeque<Entry<A>>), A::Error> {
-        let output = action.apply(target)?;
+        action: A,
+    ) -> core::result::Result<(A::Output, usize, VecDeque<Entry<A>>), A::Error> {
+        self.__apply_with(target, action, |_| ())
+    }
+
+    /// Like `__apply`, but calls `f` with the entry after it has been applied
+    /// and before it is pushed onto the record.
+    #[allow(clippy::type_complexity)]
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "apply",
+            level = "debug",
+            skip_all,
+            fields(current = self.current)
+        )
+    )]
+    pub(crate) fn __apply_with(
+        &mut self,
+        target: &mut A::Target,
+        action: A,
+        f: impl FnOnce(&Entry<A>),
+    ) -> core::result::Result<(A::Output, usize, VecDeque<Entry<A>>), A::Error> {
+        let mut entry = Entry::from(action);
+        let output = entry.apply(target, &self.guard, self.current)?;
+        f(&entry);
+        // Defer the action until the group is ended.
+        if let Some(group) = self.groups.last_mut() {
+            group.push(entry.action);
+            return Ok((output, 0, VecDeque::new()));
+        }
+        let (evicted, tail) = self.push(entry)?;
+        Ok((output, evicted, tail))
+    }
+
+    /// Pushes the entry and returns the number of evicted entries and the entries that were
+    /// popped off after the current position.
+    ///
+    /// Returns an error without changing the record if the storage can not load
+    /// the entries after the current position.
+    pub(crate) fn push(
+        &mut self,
+        entry: Entry<A>,
+    ) -> core::result::Result<(usize, VecDeque<Entry<A>>), A::Error> {
         let current = self.current();
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
         let was_saved = self.is_saved();
         // Pop off all elements after len from record.
-        let tail = self.entries.split_off(current);
+        let tail = self
+            .entries
+            .split_off(current)
+            .map_err(IntoActionError::into_action_error)?;
         // Check if the saved state was popped off.
         self.saved = self.saved.filter(|&saved| saved <= current);
-        // Try to merge actions unless the target is in a saved state.
-        let merged = match self.entries.back_mut() {
-            Some(last) if !was_saved => last.action.merge(action),
-            _ => Merged::No(action),
+        // Merge the entry into the last entry as decided by the merge policy.
+        // The entry is pushed on its own if the last entry can not be loaded.
+        let last = current
+            .checked_sub(1)
+            .and_then(|i| self.entries.get_mut(i).ok().flatten());
+        let merged = match last {
+            Some(last) => match self.policy.decide(
+                &last.info(current - 1, was_saved),
+                &entry.info(current, false),
+            ) {
+                Decision::No => Merged::No(entry),
+                Decision::Action => last.merge(entry),
+                Decision::Group => {
+                    last.append(entry);
+                    Merged::Yes
+                }
+            },
+            None => Merged::No(entry),
         };
-        let merged_or_annulled = match merged {
-            Merged::Yes => true,
+        let mut evicted = 0;
+        match merged {
+            Merged::Yes => {
+                // The saved state no longer exists if the saved entry was changed.
+                self.saved = self.saved.filter(|_| !was_saved);
+                self.slot.emit(Signal::Merged);
+                #[cfg(feature = "tracing")]
+                tracing::debug!(position = current - 1, "merged");
+            }
             Merged::Annul => {
-                self.entries.pop_back();
+                self.entries.remove_back();
                 self.current -= 1;
-                true
+                self.saved = self.saved.filter(|_| !was_saved);
+                self.slot.emit(Signal::Annulled);
+                #[cfg(feature = "tracing")]
+                tracing::debug!(position = self.current, "annulled");
             }
             // If actions are not merged or annulled push it onto the record.
-            Merged::No(action) => {
+            Merged::No(entry) => {
                 // If limit is reached, pop off the first action.
                 if self.limit() == self.current() {
-                    self.entries.pop_front();
+                    self.entries.remove_front();
                     self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+                    self.slot.emit(Signal::Evicted);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(limit = self.limit(), "evicted");
+                    evicted += 1;
                 } else {
                     self.current += 1;
                 }
-                self.entries.push_back(Entry::from(action));
-                false
+                self.entries.push_back(entry);
+                self.slot.emit(Signal::Applied(self.current - 1));
             }
-        };
+        }
+        evicted += self.trim();
         self.slot.emit_if(could_redo, Signal::Redo(false));
         self.slot.emit_if(!could_undo, Signal::Undo(true));
         self.slot.emit_if(was_saved, Signal::Saved(false));
-        Ok((output, merged_or_annulled, tail))
+        Ok((evicted, tail))
+    }
+
+    /// Evicts the oldest entries that exceed the limit, the budget or the retention period,
+    /// and returns the number of evicted entries.
+    pub(crate) fn trim(&mut self) -> usize {
+        let mut evicted = 0;
+        while self.entries.len() > self.limit() && self.current > 0 {
+            self.evict();
+            #[cfg(feature = "tracing")]
+            tracing::debug!(limit = self.limit(), "evicted");
+            evicted += 1;
+        }
+        // If the budget is exceeded, pop off the oldest actions but keep the newest one.
+        // Stop at the first action that is not held in memory, since it does not count
+        // towards the budget.
+        if let Some(budget) = self.budget {
+            let mut usage = self.memory_usage();
+            while usage > budget && self.current > 1 {
+                let Some(size) = self.entries.get(0).map(Entry::size_hint) else {
+                    break;
+                };
+                usage -= size;
+                self.evict();
+                #[cfg(feature = "tracing")]
+                tracing::debug!(budget, usage, "evicted");
+                evicted += 1;
+            }
+        }
+        // Pop off the actions that are older than the retention period but keep the newest one.
+        #[cfg(feature = "chrono")]
+        if let Some(time) = self
+            .retention
+            .and_then(|retention| Utc::now().checked_sub_signed(retention))
+        {
+            evicted += self.prune(time, 1);
+        }
+        self.entries.seek(self.current);
+        evicted
+    }
+
+    /// Disables the limit, the budget and the retention period so no entries are evicted,
+    /// and returns them so they can be restored with [`resume_eviction`](Self::resume_eviction).
+    pub(crate) fn suspend_eviction(&mut self) -> Eviction {
+        Eviction {
+            limit: mem::replace(&mut self.limit, NonZeroUsize::MAX),
+            budget: self.budget.take(),
+            #[cfg(feature = "chrono")]
+            retention: self.retention.take(),
+        }
+    }
+
+    /// Restores the eviction settings without evicting any entries.
+    ///
+    /// Call [`trim`](Self::trim) afterwards to evict the entries that exceed them.
+    pub(crate) fn resume_eviction(&mut self, eviction: Eviction) {
+        self.limit = eviction.limit;
+        self.budget = eviction.budget;
+        #[cfg(feature = "chrono")]
+        {
+            self.retention = eviction.retention;
+        }
+    }
+
+    /// Removes the oldest entry, which must have been applied.
+    pub(crate) fn evict(&mut self) {
+        self.entries.remove_front();
+        self.current -= 1;
+        self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        self.slot.emit(Signal::Evicted);
+    }
+
+    /// Removes the applied entries that were last modified before `time` from the bottom,
+    /// but keeps at least `keep` of them.
+    #[cfg(feature = "chrono")]
+    fn prune(&mut self, time: DateTime<Utc>, keep: usize) -> usize {
+        let mut pruned = 0;
+        while self.current > keep
+            && self
+                .entries
+                .get_mut(0)
+                .is_ok_and(|entry| entry.is_some_and(|entry| entry.modified() < time))
+        {
+            self.evict();
+            #[cfg(feature = "tracing")]
+            tracing::debug!(%time, "pruned");
+            pruned += 1;
+        }
+        pruned
+    }
+
+    /// Ends the most recently begun group.
+    ///
+    /// If it is the outermost group, the actions in the group are added to the record
+    /// as a single entry and the signals are emitted. Empty groups are discarded,
+    /// as are groups that can not be added because the [storage] fails to load the entries
+    /// after the current position.
+    ///
+    /// [storage]: ../storage/trait.Storage.html
+    pub fn end_group(&mut self) {
+        self.__end_group();
+    }
+
+    pub(crate) fn __end_group(&mut self) -> Option<(usize, VecDeque<Entry<A>>)> {
+        let group = self.groups.pop()?;
+        if let Some(outer) = self.groups.last_mut() {
+            outer.extend(group);
+            return None;
+        }
+        group.into_entry().and_then(|entry| self.push(entry).ok())
+    }
+
+    /// Ends all open groups.
+    pub(crate) fn end_groups(&mut self) {
+        while self.is_grouping() {
+            self.end_group();
+        }
     }
 
     /// Calls the [`undo`] method for the active action and sets
     /// the previous one as the new active one.
     ///
+    /// Any open [group](struct.Record.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
     /// # Errors
     /// If an error occur when executing [`undo`] the error is returned.
     ///
     /// [`undo`]: ../trait.Action.html#tymethod.undo
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "undo",
+            level = "debug",
+            skip_all,
+            fields(current = self.current)
+        )
+    )]
     pub fn undo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.end_groups();
         self.can_undo().then(|| {
             let was_saved = self.is_saved();
             let old = self.current();
-            let output = self.entries[self.current - 1].action.undo(target)?;
+            let entry = self
+                .entries
+                .get_mut(self.current - 1)
+                .map_err(IntoActionError::into_action_error)?
+                .unwrap();
+            let output = entry.undo(target, &self.guard, self.current - 1)?;
             self.current -= 1;
+            self.entries.seek(self.current);
             let is_saved = self.is_saved();
+            self.slot.emit(Signal::Undone(self.current));
             self.slot.emit_if(old == self.len(), Signal::Redo(true));
             self.slot.emit_if(old == 1, Signal::Undo(false));
             self.slot
@@ -234,17 +542,37 @@ impl<A: Action, F: FnMut(Signal)> Record<A, F> {
     /// Calls the [`redo`] method for the active action and sets
     /// the next one as the new active one.
     ///
+    /// Any open [group](struct.Record.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
     /// # Errors
     /// If an error occur when applying [`redo`] the error is returned.
     ///
     /// [`redo`]: trait.Action.html#method.redo
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "redo",
+            level = "debug",
+            skip_all,
+            fields(current = self.current)
+        )
+    )]
     pub fn redo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.end_groups();
         self.can_redo().then(|| {
             let was_saved = self.is_saved();
             let old = self.current();
-            let output = self.entries[self.current].action.redo(target)?;
+            let entry = self
+                .entries
+                .get_mut(self.current)
+                .map_err(IntoActionError::into_action_error)?
+                .unwrap();
+            let output = entry.redo(target, &self.guard, self.current)?;
             self.current += 1;
+            self.entries.seek(self.current);
             let is_saved = self.is_saved();
+            self.slot.emit(Signal::Redone(old));
             self.slot
                 .emit_if(old == self.len() - 1, Signal::Redo(false));
             self.slot.emit_if(old == 0, Signal::Undo(true));
@@ -254,6 +582,80 @@ impl<A: Action, F: FnMut(Signal)> Record<A, F> {
         })
     }
 
+    /// Like [`undo`], but returns an error instead of `None` if there is nothing to undo.
+    ///
+    /// # Errors
+    /// Returns [`Error::NothingToUndo`] if there is nothing to undo,
+    /// or [`Error::Action`] with the index of the action if an error occur when executing [`undo`].
+    ///
+    /// [`undo`]: struct.Record.html#method.undo
+    /// [`Error::NothingToUndo`]: ../enum.Error.html#variant.NothingToUndo
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    pub fn try_undo(
+        &mut self,
+        target: &mut A::Target,
+    ) -> core::result::Result<A::Output, Error<A::Error>> {
+        self.end_groups();
+        let current = self.current();
+        self.undo(target)
+            .ok_or(Error::NothingToUndo)?
+            .map_err(|error| Error::Action(error, current - 1))
+    }
+
+    /// Like [`redo`], but returns an error instead of `None` if there is nothing to redo.
+    ///
+    /// # Errors
+    /// Returns [`Error::NothingToRedo`] if there is nothing to redo,
+    /// or [`Error::Action`] with the index of the action if an error occur when executing [`redo`].
+    ///
+    /// [`redo`]: struct.Record.html#method.redo
+    /// [`Error::NothingToRedo`]: ../enum.Error.html#variant.NothingToRedo
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    pub fn try_redo(
+        &mut self,
+        target: &mut A::Target,
+    ) -> core::result::Result<A::Output, Error<A::Error>> {
+        self.end_groups();
+        let current = self.current();
+        self.redo(target)
+            .ok_or(Error::NothingToRedo)?
+            .map_err(|error| Error::Action(error, current))
+    }
+
+    /// Returns the approximate number of bytes used by the entries held in memory.
+    ///
+    /// This is the sum of the [size hints] of the actions in the entries.
+    /// Entries that the [storage] does not hold in memory are not counted.
+    ///
+    /// [size hints]: ../trait.Action.html#method.size_hint
+    /// [storage]: ../storage/trait.Storage.html
+    pub fn memory_usage(&self) -> usize {
+        (0..self.entries.len())
+            .filter_map(|i| self.entries.get(i))
+            .map(Entry::size_hint)
+            .sum()
+    }
+
+    /// Removes the actions that were last modified before `time` from the bottom of the record,
+    /// and returns the number of removed actions.
+    ///
+    /// Only actions that have been applied are removed, since the current state depends on them.
+    /// A [`Signal::Evicted`] is emitted for each removed action. If the target was saved
+    /// before the oldest remaining action, the saved state is removed.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    ///
+    /// [`Signal::Evicted`]: ../enum.Signal.html#variant.Evicted
+    #[cfg(feature = "chrono")]
+    pub fn prune_older_than(&mut self, time: DateTime<Utc>) -> usize {
+        let could_undo = self.can_undo();
+        let pruned = self.prune(time, 0);
+        self.entries.seek(self.current);
+        self.slot
+            .emit_if(could_undo && !self.can_undo(), Signal::Undo(false));
+        pruned
+    }
+
     /// Marks the target as currently being in a saved or unsaved state.
     pub fn set_saved(&mut self, saved: bool) {
         let was_saved = self.is_saved();
@@ -266,54 +668,148 @@ impl<A: Action, F: FnMut(Signal)> Record<A, F> {
         }
     }
 
+    /// Calls `f` with the record while holding back the signals,
+    /// and then only emits the net changes in the state.
+    ///
+    /// # Examples
+    /// ```
+    /// # use undo::{Record, Signal};
+    /// # include!("../add.rs");
+    /// # fn main() {
+    /// let mut target = String::new();
+    /// let mut record = Record::new();
+    /// record.set_saved(false);
+    /// record.connect(Box::new(|signal| assert_eq!(signal, Signal::Undo(true))));
+    /// record.batch_signals(|record| {
+    ///     record.apply(&mut target, Add('a')).unwrap();
+    ///     record.apply(&mut target, Add('b')).unwrap();
+    ///     record.apply(&mut target, Add('c')).unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub fn batch_signals<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let muted = self.slot.mute(true);
+        let r = f(self);
+        self.slot.mute(muted);
+        let can_undo = self.can_undo();
+        let can_redo = self.can_redo();
+        let is_saved = self.is_saved();
+        self.slot
+            .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
+        self.slot
+            .emit_if(could_redo != can_redo, Signal::Redo(can_redo));
+        self.slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
+        r
+    }
+
     /// Removes all actions from the record without undoing them.
     pub fn clear(&mut self) {
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
         self.entries.clear();
+        self.groups.clear();
         self.saved = self.is_saved().then_some(0);
         self.current = 0;
+        self.slot.emit(Signal::Cleared);
         self.slot.emit_if(could_undo, Signal::Undo(false));
         self.slot.emit_if(could_redo, Signal::Redo(false));
     }
-}
 
-impl<A: Action<Output = ()>, F: FnMut(Signal)> Record<A, F> {
-    /// Revert the changes done to the target since the saved state.
-    pub fn revert(&mut self, target: &mut A::Target) -> Option<Result<A>> {
-        self.saved.and_then(|saved| self.go_to(target, saved))
+    /// Like [`revert`], but calls `f` with the output of each action that is undone or redone.
+    ///
+    /// [`revert`]: struct.Record.html#method.revert
+    pub fn revert_with(
+        &mut self,
+        target: &mut A::Target,
+        f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        self.end_groups();
+        self.saved
+            .and_then(|saved| self.go_to_with(target, saved, f))
     }
 
-    /// Repeatedly calls [`undo`] or [`redo`] until the action at `current` is reached.
+    /// Like [`go_to`], but calls `f` with the output of each action that is undone or redone.
     ///
-    /// # Errors
-    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    /// # Examples
+    /// ```
+    /// # use undo::{Action, Record};
+    /// struct Add(char);
     ///
-    /// [`undo`]: trait.Action.html#tymethod.undo
-    /// [`redo`]: trait.Action.html#method.redo
-    pub fn go_to(&mut self, target: &mut A::Target, current: usize) -> Option<Result<A>> {
+    /// impl Action for Add {
+    ///     type Target = String;
+    ///     type Output = char;
+    ///     type Error = &'static str;
+    ///
+    ///     fn apply(&mut self, s: &mut String) -> undo::Result<Add> {
+    ///         s.push(self.0);
+    ///         Ok(self.0)
+    ///     }
+    ///
+    ///     fn undo(&mut self, s: &mut String) -> undo::Result<Add> {
+    ///         s.pop().ok_or("s is empty")
+    ///     }
+    /// }
+    ///
+    /// # fn main() {
+    /// let mut target = String::new();
+    /// let mut record = Record::new();
+    /// record.apply(&mut target, Add('a')).unwrap();
+    /// record.apply(&mut target, Add('b')).unwrap();
+    /// let mut outputs = Vec::new();
+    /// record.go_to_with(&mut target, 0, |c| outputs.push(c)).unwrap().unwrap();
+    /// assert_eq!(outputs, ['b', 'a']);
+    /// # }
+    /// ```
+    ///
+    /// [`go_to`]: struct.Record.html#method.go_to
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "go_to",
+            level = "debug",
+            skip_all,
+            fields(from = self.current, to = current)
+        )
+    )]
+    pub fn go_to_with(
+        &mut self,
+        target: &mut A::Target,
+        current: usize,
+        mut f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        self.end_groups();
         if current > self.len() {
             return None;
         }
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
         let was_saved = self.is_saved();
-        // Temporarily remove slot so they are not called each iteration.
-        let slot = self.disconnect();
+        let start = self.current();
+        // Temporarily mute the slot so it is not called each iteration.
+        let muted = self.slot.mute(true);
         // Decide if we need to undo or redo to reach current.
-        let f = if current > self.current() {
+        let step = if current > self.current() {
             Record::redo
         } else {
             Record::undo
         };
         while self.current() != current {
-            if let Some(Err(err)) = f(self, target) {
-                self.slot.f = slot;
-                return Some(Err(err));
+            match step(self, target).unwrap() {
+                Ok(output) => f(output),
+                Err(err) => {
+                    self.slot.mute(muted);
+                    self.slot.emit_moved(start, self.current);
+                    return Some(Err(err));
+                }
             }
         }
-        // Add slot back.
-        self.slot.f = slot;
+        // Unmute the slot.
+        self.slot.mute(muted);
+        self.slot.emit_moved(start, current);
         let can_undo = self.can_undo();
         let can_redo = self.can_redo();
         let is_saved = self.is_saved();
@@ -325,10 +821,75 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Record<A, F> {
             .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
         Some(Ok(()))
     }
+}
+
+impl<A: Action, F: FnMut(Signal)> Record<A, F> {
+    /// Undoes the action at `index` without undoing the actions applied after it,
+    /// where `0` is the index of the oldest action in the record.
+    ///
+    /// Any open [group](struct.Record.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
+    /// The action is moved to the top of the stack before it is undone, which requires it to
+    /// [commute](../trait.Action.html#method.commutes_with) with every action applied after it.
+    /// The undone action can then be redone by calling [`redo`].
+    ///
+    /// Returns `None` if the action at `index` has not been applied.
+    ///
+    /// # Errors
+    /// If the action does not commute with an action applied after it a `Conflict` error
+    /// is returned and the record is not changed.
+    /// If an error occur when executing [`undo`] the error is returned.
+    ///
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    /// [`redo`]: struct.Record.html#method.redo
+    pub fn undo_at(
+        &mut self,
+        target: &mut A::Target,
+        index: usize,
+    ) -> Option<core::result::Result<A::Output, UndoAtError<A::Error>>> {
+        self.end_groups();
+        if index >= self.current() {
+            return None;
+        }
+        let top = self.current() - 1;
+        if let Some(i) = (index + 1..=top).find(|&i| {
+            let entry = &self.entries[index];
+            !entry.commutes_with(&self.entries[i])
+        }) {
+            return Some(Err(UndoAtError::Conflict(i)));
+        }
+        // Move the action to the top of the stack.
+        let entry = self.entries.remove(index).unwrap();
+        self.entries.insert(top, entry);
+        // The states between the old and new position of the action no longer exist.
+        let current = self.current();
+        self.saved = self
+            .saved
+            .filter(|&saved| saved <= index || saved >= current);
+        self.undo(target)
+            .map(|result| result.map_err(UndoAtError::Action))
+    }
 
-    /// Go back or forward in the record to the action that was made closest to the datetime provided.
+    /// Like [`time_travel`], but calls `f` with the output of each action that is undone or redone.
+    ///
+    /// [`time_travel`]: struct.Record.html#method.time_travel
     #[cfg(feature = "chrono")]
-    pub fn time_travel(&mut self, target: &mut A::Target, to: &DateTime<Utc>) -> Option<Result<A>> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "time_travel",
+            level = "debug",
+            skip_all,
+            fields(to = %to)
+        )
+    )]
+    pub fn time_travel_with(
+        &mut self,
+        target: &mut A::Target,
+        to: &DateTime<Utc>,
+        f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
         let current = match self.entries.as_slices() {
             ([], []) => return None,
             (head, []) => head
@@ -350,11 +911,158 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Record<A, F> {
                 }
             },
         };
+        self.go_to_with(target, current, f)
+    }
+}
+
+impl<A: Action<Output = ()>, F: FnMut(Signal), S: Storage<A>> Record<A, F, S>
+where
+    S::Error: IntoActionError<A::Error>,
+{
+    /// Revert the changes done to the target since the saved state.
+    ///
+    /// Any open [group](struct.Record.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    pub fn revert(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.revert_with(target, |()| ())
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the action at `current` is reached.
+    ///
+    /// Any open [group](struct.Record.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Action.html#tymethod.undo
+    /// [`redo`]: trait.Action.html#method.redo
+    pub fn go_to(&mut self, target: &mut A::Target, current: usize) -> Option<Result<A>> {
+        self.go_to_with(target, current, |()| ())
+    }
+
+    /// Like [`go_to`], but rolls back to the position it started from if an error occurs.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned
+    /// together with any error that occurred during the rollback.
+    ///
+    /// [`go_to`]: struct.Record.html#method.go_to
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    /// [`redo`]: ../trait.Action.html#method.redo
+    pub fn go_to_atomic(
+        &mut self,
+        target: &mut A::Target,
+        current: usize,
+    ) -> Option<core::result::Result<(), RollbackError<A::Error>>> {
+        self.end_groups();
+        if current > self.len() {
+            return None;
+        }
+        let start = self.current();
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let muted = self.slot.mute(true);
+        let result = self.go_to(target, current)?.map_err(|error| {
+            let rollback = self.go_to(target, start).and_then(|result| result.err());
+            RollbackError { error, rollback }
+        });
+        self.slot.mute(muted);
+        self.slot.emit_moved(start, self.current);
+        let can_undo = self.can_undo();
+        let can_redo = self.can_redo();
+        let is_saved = self.is_saved();
+        self.slot
+            .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
+        self.slot
+            .emit_if(could_redo != can_redo, Signal::Redo(can_redo));
+        self.slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
+        Some(result)
+    }
+
+    /// Like [`go_to`], but returns an error instead of `None` if `current` is out of range.
+    ///
+    /// # Errors
+    /// Returns [`Error::OutOfRange`] if `current` is larger than the number of actions,
+    /// or [`Error::Action`] with the index of the action if an error occur
+    /// when executing [`undo`] or [`redo`].
+    ///
+    /// [`go_to`]: struct.Record.html#method.go_to
+    /// [`Error::OutOfRange`]: ../enum.Error.html#variant.OutOfRange
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    /// [`redo`]: ../trait.Action.html#method.redo
+    pub fn try_go_to(
+        &mut self,
+        target: &mut A::Target,
+        current: usize,
+    ) -> core::result::Result<(), Error<A::Error>> {
         self.go_to(target, current)
+            .ok_or(Error::OutOfRange(current))?
+            .map_err(|error| Error::Action(error, failed_at(self.current(), current)))
+    }
+}
+
+impl<A: Action<Output = ()>, F: FnMut(Signal)> Record<A, F> {
+    /// Go back or forward in the record to the action that was made closest to the datetime provided.
+    ///
+    /// Any open [group](struct.Record.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    #[cfg(feature = "chrono")]
+    pub fn time_travel(&mut self, target: &mut A::Target, to: &DateTime<Utc>) -> Option<Result<A>> {
+        self.time_travel_with(target, to, |()| ())
+    }
+}
+
+impl<A: Transform<Output = ()> + Clone, F: FnMut(Signal)> Record<A, F> {
+    /// Applies actions that were made against the state at position `base`.
+    ///
+    /// The actions are [transformed](../trait.Transform.html) past the actions applied
+    /// since `base` before they are pushed on top of the record, so the actions applied
+    /// since then are kept and can still be undone.
+    ///
+    /// Returns `None` if `base` is larger than the current position.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`apply`] the error is returned
+    /// and the remaining actions are not applied.
+    ///
+    /// [`apply`]: ../trait.Action.html#tymethod.apply
+    pub fn rebase(
+        &mut self,
+        target: &mut A::Target,
+        base: usize,
+        actions: impl IntoIterator<Item = A>,
+    ) -> Option<Result<A>> {
+        self.end_groups();
+        if base > self.current() {
+            return None;
+        }
+        // The applied actions are transformed along with the incoming actions,
+        // since each incoming action is made after the previous one.
+        let mut applied: Vec<A> = self
+            .entries
+            .range(base..self.current())
+            .flat_map(|entry| iter::once(&entry.action).chain(&entry.group))
+            .cloned()
+            .collect();
+        for mut action in actions {
+            for other in &mut applied {
+                let original = action.clone();
+                action.include(other);
+                other.include(&original);
+            }
+            if let Err(err) = self.apply(target, action) {
+                return Some(Err(err));
+            }
+        }
+        Some(Ok(()))
     }
 }
 
-impl<A: ToString, F> Record<A, F> {
+impl<A: ToString, F, S: Storage<A>> Record<A, F, S> {
     /// Returns the string of the action which will be undone
     /// in the next call to [`undo`](struct.Record.html#method.undo).
     pub fn undo_text(&self) -> Option<String> {
@@ -368,7 +1076,7 @@ impl<A: ToString, F> Record<A, F> {
     }
 
     fn text(&self, i: usize) -> Option<String> {
-        self.entries.get(i).map(|e| e.action.to_string())
+        self.entries.get(i).map(Entry::text)
     }
 }
 
@@ -384,7 +1092,7 @@ impl<A, F> From<History<A, F>> for Record<A, F> {
     }
 }
 
-impl<A: fmt::Debug, F> fmt::Debug for Record<A, F> {
+impl<A: fmt::Debug, F, S: fmt::Debug> fmt::Debug for Record<A, F, S> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Record")
             .field("entries", &self.entries)
@@ -392,6 +1100,7 @@ impl<A: fmt::Debug, F> fmt::Debug for Record<A, F> {
             .field("limit", &self.limit)
             .field("saved", &self.saved)
             .field("slot", &self.slot)
+            .field("groups", &self.groups)
             .finish()
     }
 }
@@ -412,11 +1121,16 @@ impl<A: fmt::Debug, F> fmt::Debug for Record<A, F> {
 /// # }
 /// ```
 #[derive(Debug)]
-pub struct Builder<F = Box<dyn FnMut(Signal)>> {
+pub struct Builder<F = Box<dyn FnMut(Signal)>, L = ()> {
     capacity: usize,
     limit: NonZeroUsize,
+    budget: Option<usize>,
+    #[cfg(feature = "chrono")]
+    retention: Option<Duration>,
     saved: bool,
     slot: Slot<F>,
+    policy: Policy,
+    layer: L,
 }
 
 impl<F> Builder<F> {
@@ -425,13 +1139,20 @@ impl<F> Builder<F> {
         Builder {
             capacity: 0,
             limit: NonZeroUsize::new(usize::MAX).unwrap(),
+            budget: None,
+            #[cfg(feature = "chrono")]
+            retention: None,
             saved: true,
             slot: Slot::default(),
+            policy: Policy::default(),
+            layer: (),
         }
     }
+}
 
+impl<F, L> Builder<F, L> {
     /// Sets the capacity for the record.
-    pub fn capacity(mut self, capacity: usize) -> Builder<F> {
+    pub fn capacity(mut self, capacity: usize) -> Builder<F, L> {
         self.capacity = capacity;
         self
     }
@@ -440,33 +1161,133 @@ impl<F> Builder<F> {
     ///
     /// # Panics
     /// Panics if `limit` is `0`.
-    pub fn limit(mut self, limit: usize) -> Builder<F> {
+    pub fn limit(mut self, limit: usize) -> Builder<F, L> {
         self.limit = NonZeroUsize::new(limit).expect("limit can not be `0`");
         self
     }
 
+    /// Sets the memory `budget` of the record in bytes.
+    ///
+    /// When the [size hints] of the entries in memory add up to more than `budget`,
+    /// the oldest entries are evicted. The newest entry is never evicted, and neither are
+    /// the entries from the oldest one that the [storage] does not hold in memory.
+    ///
+    /// [size hints]: ../trait.Action.html#method.size_hint
+    /// [storage]: ../storage/trait.Storage.html
+    pub fn budget(mut self, budget: usize) -> Builder<F, L> {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Sets how long the actions are kept in the record.
+    ///
+    /// When an action is pushed, the actions that were last modified more than `retention` ago
+    /// are removed. The newest action is never removed, even if `retention` is zero.
+    /// See [`Record::prune_older_than`] for more information.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    ///
+    /// [`Record::prune_older_than`]: struct.Record.html#method.prune_older_than
+    #[cfg(feature = "chrono")]
+    pub fn retention(mut self, retention: Duration) -> Builder<F, L> {
+        self.retention = Some(retention);
+        self
+    }
+
     /// Sets if the target is initially in a saved state.
     /// By default the target is in a saved state.
-    pub fn saved(mut self, saved: bool) -> Builder<F> {
+    pub fn saved(mut self, saved: bool) -> Builder<F, L> {
         self.saved = saved;
         self
     }
 
+    /// Sets the policy that decides if and how actions are merged.
+    ///
+    /// The policy decides if the actions are merged by [`Action::merge`], combined into
+    /// a single entry, or not merged at all.
+    /// By default actions are merged by [`Action::merge`] unless the target is in a saved state.
+    ///
+    /// [`Action::merge`]: ../trait.Action.html#method.merge
+    pub fn merge_policy(
+        mut self,
+        policy: impl MergePolicy + Send + Sync + 'static,
+    ) -> Builder<F, L> {
+        self.policy = Policy::new(policy);
+        self
+    }
+
+    /// Sets the interval in which consecutive actions are allowed to be merged.
+    ///
+    /// An action is only merged with the last entry if it is applied within `interval`
+    /// of when the entry was last modified. This is a shorthand for using the
+    /// [`TimeWindow`](../merge/struct.TimeWindow.html) merge policy.
+    ///
+    /// Requires the `chrono` feature to be enabled.
+    #[cfg(feature = "chrono")]
+    pub fn merge_interval(self, interval: Duration) -> Builder<F, L> {
+        self.merge_policy(TimeWindow::new(interval))
+    }
+
+    /// Adds a layer that wraps the calls made to the actions.
+    ///
+    /// The first layer added is the outermost one.
+    pub fn layer<M>(self, layer: M) -> Builder<F, Stack<M, L>> {
+        Builder {
+            capacity: self.capacity,
+            limit: self.limit,
+            budget: self.budget,
+            #[cfg(feature = "chrono")]
+            retention: self.retention,
+            saved: self.saved,
+            slot: self.slot,
+            policy: self.policy,
+            layer: Stack::new(layer, self.layer),
+        }
+    }
+
+    /// Adds an interceptor that decides if actions are allowed to be applied, undone or redone.
+    ///
+    /// This is a shorthand for adding the interceptor as a layer.
+    pub fn intercept<I>(self, interceptor: I) -> Builder<F, Stack<Intercept<I>, L>> {
+        self.layer(Intercept(interceptor))
+    }
+
     /// Builds the record.
-    pub fn build<A>(self) -> Record<A, F> {
+    pub fn build<A>(self) -> Record<A, F>
+    where
+        L: Layer<A> + Send + Sync + 'static,
+    {
+        let entries = VecDeque::with_capacity(self.capacity);
+        self.build_with(entries)
+    }
+
+    /// Builds the record with the entries held in `storage`.
+    ///
+    /// Any entries already in `storage` are removed.
+    pub fn build_with<A, S: Storage<A>>(self, mut storage: S) -> Record<A, F, S>
+    where
+        L: Layer<A> + Send + Sync + 'static,
+    {
+        storage.clear();
         Record {
-            entries: VecDeque::with_capacity(self.capacity),
+            entries: storage,
             current: 0,
             limit: self.limit,
+            budget: self.budget,
+            #[cfg(feature = "chrono")]
+            retention: self.retention,
             saved: self.saved.then_some(0),
             slot: self.slot,
+            groups: Vec::new(),
+            policy: self.policy,
+            guard: Guard::new(self.layer),
         }
     }
 }
 
-impl<F: FnMut(Signal)> Builder<F> {
+impl<F: FnMut(Signal), L> Builder<F, L> {
     /// Connects the slot.
-    pub fn connect(mut self, f: F) -> Builder<F> {
+    pub fn connect(mut self, f: F) -> Builder<F, L> {
         self.slot = Slot::from(f);
         self
     }
@@ -509,7 +1330,7 @@ pub struct Queue<'a, A, F> {
     actions: Vec<QueueAction<A>>,
 }
 
-impl<A: Action<Output = ()>, F: FnMut(Signal)> Queue<'_, A, F> {
+impl<A: Action, F: FnMut(Signal)> Queue<'_, A, F> {
     /// Queues an `apply` action.
     pub fn apply(&mut self, action: A) {
         self.actions.push(QueueAction::Apply(action));
@@ -525,39 +1346,133 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Queue<'_, A, F> {
         self.actions.push(QueueAction::Redo);
     }
 
-    /// Applies the queued actions.
+    /// Applies the queued actions and calls `f` with the output of each of them.
     ///
     /// # Errors
     /// If an error occurs, it stops applying the actions and returns the error.
-    pub fn commit(self, target: &mut A::Target) -> Option<Result<A>> {
-        for action in self.actions {
-            let r = match action {
-                QueueAction::Apply(action) => Some(self.record.apply(target, action)),
-                QueueAction::Undo => self.record.undo(target),
-                QueueAction::Redo => self.record.redo(target),
-            };
-            match r {
-                Some(Ok(_)) => (),
-                o @ Some(Err(_)) | o @ None => return o,
+    pub fn commit_with(
+        self,
+        target: &mut A::Target,
+        mut f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        let actions = self.actions;
+        self.record.batch_signals(|record| {
+            for action in actions {
+                let r = match action {
+                    QueueAction::Apply(action) => Some(record.apply(target, action)),
+                    QueueAction::Undo => record.undo(target),
+                    QueueAction::Redo => record.redo(target),
+                };
+                match r {
+                    Some(Ok(output)) => f(output),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => return None,
+                }
             }
-        }
-        Some(Ok(()))
+            Some(Ok(()))
+        })
     }
 
     /// Cancels the queued actions.
     pub fn cancel(self) {}
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<A, F> {
+    pub fn queue(&mut self) -> Queue<'_, A, F> {
         self.record.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<A, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, A, F> {
         self.record.checkpoint()
     }
 }
 
+impl<A: Action<Output = ()>, F: FnMut(Signal)> Queue<'_, A, F> {
+    /// Applies the queued actions.
+    ///
+    /// # Errors
+    /// If an error occurs, it stops applying the actions and returns the error.
+    pub fn commit(self, target: &mut A::Target) -> Option<Result<A>> {
+        self.commit_with(target, |()| ())
+    }
+
+    /// Applies all the queued actions, or none of them.
+    ///
+    /// If an error occurs, the actions that were already applied are rolled back so
+    /// both the target and the record are returned to the state they were in before the commit.
+    /// To make this possible, the actions are not merged during the commit, and entries
+    /// are only evicted after all the actions have succeeded.
+    ///
+    /// Returns `None` without applying any actions if a queued `undo` or `redo` action
+    /// can not be performed.
+    ///
+    /// # Errors
+    /// If an error occurs, it rolls back the applied actions and returns the error
+    /// together with any error that occurred during the rollback.
+    pub fn commit_atomic(
+        self,
+        target: &mut A::Target,
+    ) -> Option<core::result::Result<(), RollbackError<A::Error>>> {
+        self.record.end_groups();
+        if !self.can_commit() {
+            return None;
+        }
+        let policy = mem::replace(&mut self.record.policy, Policy::new(Never));
+        let actions = self.actions;
+        let result = self.record.batch_signals(|record| {
+            let eviction = record.suspend_eviction();
+            let mut checkpoint = record.checkpoint();
+            let mut result = Ok(());
+            for action in actions {
+                let o = match action {
+                    QueueAction::Apply(action) => checkpoint.apply(target, action),
+                    QueueAction::Undo => checkpoint.undo(target).unwrap(),
+                    QueueAction::Redo => checkpoint.redo(target).unwrap(),
+                };
+                if let Err(error) = o {
+                    let rollback = checkpoint.cancel(target).and_then(|o| o.err());
+                    result = Err(RollbackError { error, rollback });
+                    break;
+                }
+            }
+            record.resume_eviction(eviction);
+            if result.is_ok() {
+                record.trim();
+            }
+            result
+        });
+        self.record.policy = policy;
+        Some(result)
+    }
+}
+
+impl<A, F> Queue<'_, A, F> {
+    /// Returns `true` if all the queued `undo` and `redo` actions can be performed.
+    ///
+    /// The actions are neither merged nor evicted during the commit,
+    /// so each applied action adds an entry.
+    fn can_commit(&self) -> bool {
+        let mut current = self.record.current();
+        let mut len = self.record.len();
+        self.actions.iter().all(|action| match action {
+            QueueAction::Apply(_) => {
+                current += 1;
+                len = current;
+                true
+            }
+            QueueAction::Undo if current > 0 => {
+                current -= 1;
+                true
+            }
+            QueueAction::Redo if current < len => {
+                current += 1;
+                true
+            }
+            QueueAction::Undo | QueueAction::Redo => false,
+        })
+    }
+}
+
 impl<'a, A, F> From<&'a mut Record<A, F>> for Queue<'a, A, F> {
     fn from(record: &'a mut Record<A, F>) -> Self {
         Queue {
@@ -581,19 +1496,19 @@ pub struct Checkpoint<'a, A, F> {
     actions: Vec<CheckpointAction<A>>,
 }
 
-impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
+impl<A: Action, F: FnMut(Signal)> Checkpoint<'_, A, F> {
     /// Calls the `apply` method.
     pub fn apply(&mut self, target: &mut A::Target, action: A) -> Result<A> {
         let saved = self.record.saved;
-        let (_, _, tail) = self.record.__apply(target, action)?;
+        let (output, _, tail) = self.record.__apply(target, action)?;
         self.actions.push(CheckpointAction::Apply(saved, tail));
-        Ok(())
+        Ok(output)
     }
 
     /// Calls the `undo` method.
     pub fn undo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
         match self.record.undo(target) {
-            o @ Some(Ok(())) => {
+            o @ Some(Ok(_)) => {
                 self.actions.push(CheckpointAction::Undo);
                 o
             }
@@ -604,7 +1519,7 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
     /// Calls the `redo` method.
     pub fn redo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
         match self.record.redo(target) {
-            o @ Some(Ok(())) => {
+            o @ Some(Ok(_)) => {
                 self.actions.push(CheckpointAction::Redo);
                 o
             }
@@ -615,46 +1530,69 @@ impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
     /// Commits the changes and consumes the checkpoint.
     pub fn commit(self) {}
 
-    /// Cancels the changes and consumes the checkpoint.
+    /// Cancels the changes, calls `f` with the output of each action that is
+    /// undone or redone, and consumes the checkpoint.
     ///
     /// # Errors
     /// If an error occur when canceling the changes, the error is returned
     /// and the remaining actions are not canceled.
-    pub fn cancel(self, target: &mut A::Target) -> Option<Result<A>> {
-        for action in self.actions.into_iter().rev() {
-            match action {
-                CheckpointAction::Apply(saved, mut entries) => match self.record.undo(target) {
-                    Some(Ok(())) => {
-                        self.record.entries.pop_back();
-                        self.record.entries.append(&mut entries);
-                        self.record.saved = saved;
-                    }
-                    o => return o,
-                },
-                CheckpointAction::Undo => match self.record.redo(target) {
-                    Some(Ok(())) => (),
-                    o => return o,
-                },
-                CheckpointAction::Redo => match self.record.undo(target) {
-                    Some(Ok(())) => (),
-                    o => return o,
-                },
-            };
-        }
-        Some(Ok(()))
+    pub fn cancel_with(
+        self,
+        target: &mut A::Target,
+        mut f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        let actions = self.actions;
+        self.record.batch_signals(|record| {
+            for action in actions.into_iter().rev() {
+                match action {
+                    CheckpointAction::Apply(saved, mut entries) => match record.undo(target) {
+                        Some(Ok(output)) => {
+                            f(output);
+                            record.entries.pop_back();
+                            record.entries.append(&mut entries);
+                            record.saved = saved;
+                        }
+                        Some(Err(err)) => return Some(Err(err)),
+                        None => return None,
+                    },
+                    CheckpointAction::Undo => match record.redo(target) {
+                        Some(Ok(output)) => f(output),
+                        Some(Err(err)) => return Some(Err(err)),
+                        None => return None,
+                    },
+                    CheckpointAction::Redo => match record.undo(target) {
+                        Some(Ok(output)) => f(output),
+                        Some(Err(err)) => return Some(Err(err)),
+                        None => return None,
+                    },
+                };
+            }
+            Some(Ok(()))
+        })
     }
 
     /// Returns a queue.
-    pub fn queue(&mut self) -> Queue<A, F> {
+    pub fn queue(&mut self) -> Queue<'_, A, F> {
         self.record.queue()
     }
 
     /// Returns a checkpoint.
-    pub fn checkpoint(&mut self) -> Checkpoint<A, F> {
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, A, F> {
         self.record.checkpoint()
     }
 }
 
+impl<A: Action<Output = ()>, F: FnMut(Signal)> Checkpoint<'_, A, F> {
+    /// Cancels the changes and consumes the checkpoint.
+    ///
+    /// # Errors
+    /// If an error occur when canceling the changes, the error is returned
+    /// and the remaining actions are not canceled.
+    pub fn cancel(self, target: &mut A::Target) -> Option<Result<A>> {
+        self.cancel_with(target, |()| ())
+    }
+}
+
 impl<'a, A, F> From<&'a mut Record<A, F>> for Checkpoint<'a, A, F> {
     fn from(record: &'a mut Record<A, F>) -> Self {
         Checkpoint {
@@ -758,7 +1696,7 @@ impl<A: fmt::Display, F> fmt::Display for Display<'_, A, F> {
 #[cfg(test)]
 mod tests {
     use crate::*;
-    use alloc::string::String;
+    use alloc::{boxed::Box, string::String};
 
     enum Edit {
         Add(Add),
@@ -966,15 +1904,829 @@ mod tests {
         assert_eq!(target, "abc");
     }
 
+    #[test]
+    fn group() {
+        let mut target = String::new();
+        let mut record = Record::new();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.begin_group(Some(String::from("bcd")));
+        record.apply(&mut target, Add('b')).unwrap();
+        record.begin_group(None);
+        record.apply(&mut target, Add('c')).unwrap();
+        record.end_group();
+        record.apply(&mut target, Add('d')).unwrap();
+        assert_eq!(target, "abcd");
+        assert_eq!(record.len(), 1);
+        record.end_group();
+        assert!(!record.is_grouping());
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 2);
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "a");
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abcd");
+        record.begin_group(None);
+        record.end_group();
+        assert_eq!(record.len(), 2);
+    }
+
+    #[test]
+    fn group_rollback() {
+        let mut target = String::new();
+        let mut record = Record::new();
+        record.begin_group(None);
+        record.apply(&mut target, Flaky('a', true)).unwrap();
+        record.apply(&mut target, Flaky('b', false)).unwrap();
+        record.apply(&mut target, Flaky('c', true)).unwrap();
+        record.end_group();
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "");
+        assert_eq!(record.redo(&mut target), Some(Err("redo failed")));
+        assert_eq!(target, "");
+        assert_eq!(record.current(), 0);
+    }
+
     #[test]
     fn annul() {
         let mut target = String::new();
         let mut record = Record::new();
         record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
+        record.apply(&mut target, Edit::Del(Del(None))).unwrap();
+        record.apply(&mut target, Edit::Add(Add('b'))).unwrap();
+        assert_eq!(record.len(), 1);
+    }
+
+    #[test]
+    fn merge_policy() {
+        let mut target = String::new();
+        let mut record: Record<_> = record::Builder::new().merge_policy(merge::Never).build();
+        record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
+        record.apply(&mut target, Edit::Del(Del(None))).unwrap();
+        assert_eq!(record.len(), 2);
+        let mut record: Record<_> = record::Builder::new().merge_policy(merge::Always).build();
+        record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
+        record.set_saved(true);
+        record.apply(&mut target, Edit::Del(Del(None))).unwrap();
+        assert_eq!(record.len(), 0);
+        assert!(!record.is_saved());
+    }
+
+    #[test]
+    fn merge_group() {
+        let mut target = String::new();
+        let mut record: Record<_> = record::Builder::new()
+            .merge_policy(merge::Decision::Group)
+            .build();
+        record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
+        record.apply(&mut target, Edit::Add(Add('b'))).unwrap();
+        record.apply(&mut target, Edit::Del(Del(None))).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(target, "a");
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "");
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "a");
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn merge_interval() {
+        let mut target = String::new();
+        let mut record: Record<_> = record::Builder::new()
+            .merge_interval(chrono::Duration::zero())
+            .build();
+        record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
+        record.apply(&mut target, Edit::Del(Del(None))).unwrap();
+        assert_eq!(record.len(), 2);
+        let mut record: Record<_> = record::Builder::new()
+            .merge_interval(chrono::Duration::hours(1))
+            .build();
+        record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
+        record.apply(&mut target, Edit::Del(Del(None))).unwrap();
+        assert_eq!(record.len(), 0);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn prune_older_than() {
+        use alloc::rc::Rc;
+        use chrono::{Duration, Utc};
+        use core::cell::RefCell;
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let sink = signals.clone();
+        let mut target = String::new();
+        let mut record = record::Builder::new()
+            .connect(
+                Box::new(move |signal| sink.borrow_mut().push(signal)) as Box<dyn FnMut(Signal)>
+            )
+            .build();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.apply(&mut target, Add('b')).unwrap();
+        record.apply(&mut target, Add('c')).unwrap();
+        record.set_saved(true);
+        record.apply(&mut target, Add('d')).unwrap();
+        record.undo(&mut target).unwrap().unwrap();
+        let now = Utc::now();
+        for (i, entry) in record.entries.iter_mut().enumerate() {
+            entry.timestamp = now + Duration::hours(i as i64);
+        }
+        signals.borrow_mut().clear();
+
+        assert_eq!(record.prune_older_than(now + Duration::minutes(90)), 2);
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 1);
+        assert!(record.is_saved());
+        assert_eq!(*signals.borrow(), [Signal::Evicted, Signal::Evicted]);
+
+        // The undone action is kept.
+        signals.borrow_mut().clear();
+        assert_eq!(record.prune_older_than(now + Duration::hours(10)), 1);
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 0);
+        assert!(record.is_saved());
+        assert_eq!(*signals.borrow(), [Signal::Evicted, Signal::Undo(false)]);
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abcd");
+
+        let mut record = record::Builder::default()
+            .retention(Duration::hours(1))
+            .build();
+        record.apply(&mut target, Add('e')).unwrap();
+        record.entries[0].timestamp = now - Duration::hours(2);
+        record.apply(&mut target, Add('f')).unwrap();
+        assert_eq!(record.len(), 1);
+        assert_eq!(record.current(), 1);
+        assert!(!record.is_saved());
+
+        // The action that was just pushed is kept.
+        let mut record = record::Builder::default()
+            .retention(Duration::zero())
+            .build();
+        record.apply(&mut target, Add('g')).unwrap();
+        record.apply(&mut target, Add('h')).unwrap();
+        assert_eq!(record.len(), 1);
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abcdefg");
+    }
+
+    struct Inc(i32);
+
+    impl Action for Inc {
+        type Target = i32;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, i: &mut i32) -> Result<Inc> {
+            *i += self.0;
+            Ok(())
+        }
+
+        fn undo(&mut self, i: &mut i32) -> Result<Inc> {
+            *i -= self.0;
+            Ok(())
+        }
+
+        fn commutes_with(&self, _: &Inc) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn undo_at() {
+        let mut target = 0;
+        let mut record = Record::new();
+        record.apply(&mut target, Inc(1)).unwrap();
+        record.apply(&mut target, Inc(10)).unwrap();
+        record.apply(&mut target, Inc(100)).unwrap();
+        record.undo_at(&mut target, 0).unwrap().unwrap();
+        assert_eq!(target, 110);
+        assert_eq!(record.current(), 2);
+        assert!(record.undo_at(&mut target, 2).is_none());
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, 10);
+        record.redo(&mut target).unwrap().unwrap();
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, 111);
+
+        let mut target = String::new();
+        let mut record = Record::new();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.apply(&mut target, Add('b')).unwrap();
+        assert_eq!(
+            record.undo_at(&mut target, 0).unwrap(),
+            Err(UndoAtError::Conflict(1))
+        );
+        assert_eq!(target, "ab");
+    }
+
+    #[derive(Clone)]
+    struct Insert(usize, char);
+
+    impl Action for Insert {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Insert> {
+            s.insert(self.0, self.1);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Insert> {
+            s.remove(self.0);
+            Ok(())
+        }
+    }
+
+    impl Transform for Insert {
+        fn include(&mut self, other: &Insert) {
+            if other.0 < self.0 || (other.0 == self.0 && other.1 < self.1) {
+                self.0 += 1;
+            }
+        }
+
+        fn exclude(&mut self, other: &Insert) {
+            if other.0 < self.0 {
+                self.0 -= 1;
+            }
+        }
+    }
+
+    #[test]
+    fn rebase() {
+        let mut a = String::from("ac");
+        let mut b = a.clone();
+        let mut record_a = Record::new();
+        let mut record_b = Record::new();
+        record_a.apply(&mut a, Insert(1, 'b')).unwrap();
+        record_a.apply(&mut a, Insert(3, 'd')).unwrap();
+        record_b.apply(&mut b, Insert(0, 'x')).unwrap();
+        record_b.apply(&mut b, Insert(3, 'y')).unwrap();
+        assert_eq!(a, "abcd");
+        assert_eq!(b, "xacy");
+
+        record_a
+            .rebase(&mut a, 0, [Insert(0, 'x'), Insert(3, 'y')])
+            .unwrap()
+            .unwrap();
+        record_b
+            .rebase(&mut b, 0, [Insert(1, 'b'), Insert(3, 'd')])
+            .unwrap()
+            .unwrap();
+        assert_eq!(a, "xabcdy");
+        assert_eq!(a, b);
+        assert_eq!(record_a.len(), 4);
+        assert!(record_a.rebase(&mut a, 5, []).is_none());
+
+        record_a.go_to(&mut a, 2).unwrap().unwrap();
+        assert_eq!(a, "abcd");
+    }
+
+    /// Pushes a char, but can only be redone if the flag is set.
+    struct Flaky(char, bool);
+
+    impl Action for Flaky {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Flaky> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Flaky> {
+            s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+
+        fn redo(&mut self, s: &mut String) -> Result<Flaky> {
+            if self.1 {
+                self.apply(s)
+            } else {
+                Err("redo failed")
+            }
+        }
+    }
+
+    #[test]
+    fn go_to_atomic() {
+        let mut target = String::new();
+        let mut record = Record::new();
+        record.apply(&mut target, Flaky('a', true)).unwrap();
+        record.apply(&mut target, Flaky('b', false)).unwrap();
+        record.apply(&mut target, Flaky('c', true)).unwrap();
+        record.go_to(&mut target, 0).unwrap().unwrap();
+        assert_eq!(
+            record.go_to_atomic(&mut target, 3).unwrap(),
+            Err(RollbackError {
+                error: "redo failed",
+                rollback: None
+            })
+        );
+        assert_eq!(target, "");
+        assert_eq!(record.current(), 0);
+        record.go_to_atomic(&mut target, 1).unwrap().unwrap();
+        assert_eq!(target, "a");
+        assert!(record.go_to_atomic(&mut target, 4).is_none());
+    }
+
+    /// Pushes a char, but fails if the string already has three chars.
+    struct Limited(char);
+
+    impl Action for Limited {
+        type Target = String;
+        type Output = ();
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Limited> {
+            if s.len() >= 3 {
+                return Err("s is full");
+            }
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Limited> {
+            s.pop().ok_or("s is empty")?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn commit_atomic() {
+        let mut target = String::new();
+        let mut record = Record::new();
+        record.apply(&mut target, Limited('a')).unwrap();
+        record.apply(&mut target, Limited('x')).unwrap();
+        record.undo(&mut target).unwrap().unwrap();
+
+        let mut queue = record.queue();
+        queue.undo();
+        queue.undo();
+        assert!(queue.commit_atomic(&mut target).is_none());
+        assert_eq!(target, "a");
+
+        let mut queue = record.queue();
+        queue.apply(Limited('b'));
+        queue.apply(Limited('c'));
+        queue.undo();
+        queue.redo();
+        queue.apply(Limited('d'));
+        assert_eq!(
+            queue.commit_atomic(&mut target).unwrap(),
+            Err(RollbackError {
+                error: "s is full",
+                rollback: None
+            })
+        );
+        assert_eq!(target, "a");
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 1);
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "ax");
+
+        let mut queue = record.queue();
+        queue.undo();
+        queue.apply(Limited('b'));
+        queue.apply(Limited('c'));
+        queue.commit_atomic(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abc");
+        assert_eq!(record.len(), 3);
+    }
+
+    #[test]
+    fn commit_atomic_at_limit() {
+        let mut target = String::new();
+        let mut record: Record<_> = record::Builder::new().limit(2).build();
+        record.apply(&mut target, Limited('a')).unwrap();
+        record.apply(&mut target, Limited('b')).unwrap();
+
+        let mut queue = record.queue();
+        queue.apply(Limited('c'));
+        queue.apply(Limited('d'));
+        assert_eq!(
+            queue.commit_atomic(&mut target).unwrap(),
+            Err(RollbackError {
+                error: "s is full",
+                rollback: None
+            })
+        );
+        assert_eq!(target, "ab");
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 2);
+        record.undo(&mut target).unwrap().unwrap();
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "");
+        record.redo(&mut target).unwrap().unwrap();
+        record.redo(&mut target).unwrap().unwrap();
+
+        let mut queue = record.queue();
+        queue.apply(Limited('c'));
+        queue.commit_atomic(&mut target).unwrap().unwrap();
+        assert_eq!(target, "abc");
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 2);
+        record.undo(&mut target).unwrap().unwrap();
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "a");
+        assert!(!record.can_undo());
+
+        // The entries are not evicted until the commit is done,
+        // so the actions can be undone past the limit.
+        let mut target = String::new();
+        let mut record: Record<_> = record::Builder::new().limit(1).build();
+        let mut queue = record.queue();
+        queue.apply(Limited('a'));
+        queue.apply(Limited('b'));
+        queue.undo();
+        queue.undo();
+        queue.commit_atomic(&mut target).unwrap().unwrap();
+        assert_eq!(target, "");
+        assert_eq!(record.current(), 0);
+        assert!(!record.can_undo());
+    }
+
+    #[test]
+    fn try_methods() {
+        let mut target = String::new();
+        let mut record = Record::new();
+        assert_eq!(record.try_undo(&mut target), Err(Error::NothingToUndo));
+        record.apply(&mut target, Flaky('a', false)).unwrap();
+        record.try_undo(&mut target).unwrap();
+        assert_eq!(record.try_go_to(&mut target, 2), Err(Error::OutOfRange(2)));
+        assert_eq!(
+            record.try_redo(&mut target),
+            Err(Error::Action("redo failed", 0))
+        );
+        assert_eq!(
+            record.try_go_to(&mut target, 1),
+            Err(Error::Action("redo failed", 0))
+        );
+        record.clear();
+        assert_eq!(record.try_redo(&mut target), Err(Error::NothingToRedo));
+
+        record.apply(&mut target, Flaky('a', false)).unwrap();
+        record.apply(&mut target, Flaky('b', false)).unwrap();
+        target.clear();
+        assert_eq!(
+            record.try_undo(&mut target),
+            Err(Error::Action("s is empty", 1))
+        );
+        assert_eq!(
+            record.try_go_to(&mut target, 0),
+            Err(Error::Action("s is empty", 1))
+        );
+    }
+
+    #[test]
+    fn send() {
+        fn is_send<T: Send>() {}
+        is_send::<Record<Add, fn(Signal)>>();
+        is_send::<History<Add, fn(Signal)>>();
+        is_send::<Timeline<Add, fn(Signal), 32>>();
+    }
+
+    #[test]
+    fn sync() {
+        fn is_sync<T: Sync>() {}
+        is_sync::<Record<Add, fn(Signal)>>();
+        is_sync::<History<Add, fn(Signal)>>();
+        is_sync::<Timeline<Add, fn(Signal), 32>>();
+    }
+
+    /// Pushes a char and returns it as the output.
+    struct Push(char);
+
+    impl Action for Push {
+        type Target = String;
+        type Output = char;
+        type Error = &'static str;
+
+        fn apply(&mut self, s: &mut String) -> Result<Push> {
+            s.push(self.0);
+            Ok(self.0)
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Push> {
+            s.pop().ok_or("s is empty")
+        }
+    }
+
+    #[test]
+    fn outputs() {
+        let mut target = String::new();
+        let mut record = Record::new();
+        let mut outputs = Vec::new();
+        let mut queue = record.queue();
+        queue.apply(Push('a'));
+        queue.apply(Push('b'));
+        queue.undo();
+        queue
+            .commit_with(&mut target, |c| outputs.push(c))
+            .unwrap()
+            .unwrap();
+        assert_eq!(outputs, ['a', 'b', 'b']);
+
+        outputs.clear();
+        record.set_saved(true);
         record
-            .apply(&mut target, Edit::Del(Del::default()))
+            .go_to_with(&mut target, 2, |c| outputs.push(c))
+            .unwrap()
+            .unwrap();
+        record
+            .revert_with(&mut target, |c| outputs.push(c))
+            .unwrap()
+            .unwrap();
+        assert_eq!(outputs, ['b', 'b']);
+
+        outputs.clear();
+        let mut checkpoint = record.checkpoint();
+        assert_eq!(checkpoint.apply(&mut target, Push('c')), Ok('c'));
+        assert_eq!(checkpoint.undo(&mut target).unwrap(), Ok('c'));
+        assert_eq!(checkpoint.undo(&mut target).unwrap(), Ok('a'));
+        checkpoint
+            .cancel_with(&mut target, |c| outputs.push(c))
+            .unwrap()
             .unwrap();
+        assert_eq!(outputs, ['a', 'c', 'c']);
+        assert_eq!(target, "a");
+    }
+
+    #[test]
+    fn signals() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let sink = signals.clone();
+        let mut target = String::new();
+        let mut record = record::Builder::new()
+            .limit(2)
+            .connect(
+                Box::new(move |signal| sink.borrow_mut().push(signal)) as Box<dyn FnMut(Signal)>
+            )
+            .build();
+        record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
         record.apply(&mut target, Edit::Add(Add('b'))).unwrap();
+        record.apply(&mut target, Edit::Add(Add('c'))).unwrap();
+        record.apply(&mut target, Edit::Del(Del(None))).unwrap();
+        record.go_to(&mut target, 0).unwrap().unwrap();
+        record.clear();
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::Applied(0),
+                Signal::Undo(true),
+                Signal::Saved(false),
+                Signal::Applied(1),
+                Signal::Evicted,
+                Signal::Applied(1),
+                Signal::Annulled,
+                Signal::Undone(0),
+                Signal::Undo(false),
+                Signal::Redo(true),
+                Signal::Cleared,
+                Signal::Redo(false),
+            ]
+        );
+
+        signals.borrow_mut().clear();
+        let sink = signals.clone();
+        record.connect(Box::new(crate::coarse(move |signal| {
+            sink.borrow_mut().push(signal)
+        })));
+        record.apply(&mut target, Edit::Add(Add('a'))).unwrap();
+        assert_eq!(*signals.borrow(), [Signal::Undo(true)]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn subscribe() {
+        use alloc::sync::Arc;
+        use std::sync::Mutex;
+
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        let mut target = String::new();
+        let mut record = Record::new();
+        let sink = first.clone();
+        let a = record.subscribe(move |signal| sink.lock().unwrap().push(signal));
+        let sink = second.clone();
+        let b = record.subscribe(move |signal| sink.lock().unwrap().push(signal));
+        record.apply(&mut target, Add('a')).unwrap();
+        assert!(record.unsubscribe(a));
+        assert!(!record.unsubscribe(a));
+        record.apply(&mut target, Add('b')).unwrap();
+        record.go_to(&mut target, 0).unwrap().unwrap();
+        assert_eq!(
+            *first.lock().unwrap(),
+            [Signal::Applied(0), Signal::Undo(true), Signal::Saved(false)]
+        );
+        assert_eq!(
+            second.lock().unwrap()[3..],
+            [
+                Signal::Applied(1),
+                Signal::Undone(1),
+                Signal::Undone(0),
+                Signal::Undo(false),
+                Signal::Redo(true),
+                Signal::Saved(true),
+            ]
+        );
+        assert!(record.unsubscribe(b));
+    }
+
+    #[test]
+    fn batch_signals() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let sink = signals.clone();
+        let mut target = String::new();
+        let mut record = Record::new();
+        record.connect(Box::new(move |signal| sink.borrow_mut().push(signal)));
+        let mut queue = record.queue();
+        queue.apply(Add('a'));
+        queue.apply(Add('b'));
+        queue.undo();
+        queue.commit(&mut target).unwrap().unwrap();
+        assert_eq!(
+            *signals.borrow(),
+            [Signal::Undo(true), Signal::Redo(true), Signal::Saved(false)]
+        );
+
+        signals.borrow_mut().clear();
+        let mut checkpoint = record.checkpoint();
+        checkpoint.undo(&mut target).unwrap().unwrap();
+        checkpoint.cancel(&mut target).unwrap().unwrap();
+        assert_eq!(
+            *signals.borrow(),
+            [
+                Signal::Undone(0),
+                Signal::Undo(false),
+                Signal::Saved(true),
+                Signal::Undo(true),
+                Signal::Saved(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn intercept() {
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicBool, Ordering};
+
+        struct ReadOnly(Arc<AtomicBool>);
+
+        impl Interceptor<Add> for ReadOnly {
+            fn apply(&self, _: &Add, _: usize) -> Result<Add> {
+                match self.0.load(Ordering::Relaxed) {
+                    true => Err("read-only"),
+                    false => Ok(()),
+                }
+            }
+
+            fn undo(&self, _: &Add, at: usize) -> Result<Add> {
+                match at {
+                    0 => Err("can not undo first"),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        let read_only = Arc::new(AtomicBool::new(false));
+        let mut target = String::new();
+        let mut record = record::Builder::default()
+            .intercept(ReadOnly(read_only.clone()))
+            .build();
+        record.apply(&mut target, Add('a')).unwrap();
+        record.apply(&mut target, Add('b')).unwrap();
+        read_only.store(true, Ordering::Relaxed);
+        assert_eq!(record.apply(&mut target, Add('c')), Err("read-only"));
+        assert_eq!(target, "ab");
+        assert_eq!(record.len(), 2);
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(record.undo(&mut target), Some(Err("can not undo first")));
+        assert_eq!(target, "a");
+        assert_eq!(record.current(), 1);
+        record.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "ab");
+    }
+
+    #[test]
+    fn intercept_group() {
+        use crate::layer::{Context, Layer, Next};
+        use alloc::sync::Arc;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        struct NoRedoC;
+
+        impl Interceptor<Add> for NoRedoC {
+            fn redo(&self, action: &Add, _: usize) -> Result<Add> {
+                match action.0 {
+                    'c' => Err("can not redo c"),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        struct Count(Arc<AtomicUsize>);
+
+        impl Layer<Add> for Count {
+            fn call(
+                &self,
+                _: &Context,
+                action: &mut Add,
+                target: &mut String,
+                next: Next<Add>,
+            ) -> Result<Add> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                next.run(action, target)
+            }
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut target = String::new();
+        let mut record = record::Builder::default()
+            .layer(Count(calls.clone()))
+            .intercept(NoRedoC)
+            .build();
+        record.begin_group(None);
+        record.apply(&mut target, Add('a')).unwrap();
+        record.apply(&mut target, Add('b')).unwrap();
+        record.apply(&mut target, Add('c')).unwrap();
+        record.end_group();
+        record.undo(&mut target).unwrap().unwrap();
+        assert_eq!(target, "");
+        calls.store(0, Ordering::Relaxed);
+        assert_eq!(record.redo(&mut target), Some(Err("can not redo c")));
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        assert_eq!(target, "");
+        assert_eq!(record.current(), 0);
+    }
+
+    #[test]
+    fn budget() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        struct Image(char, usize);
+
+        impl Action for Image {
+            type Target = String;
+            type Output = ();
+            type Error = &'static str;
+
+            fn apply(&mut self, s: &mut String) -> Result<Image> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<Image> {
+                self.0 = s.pop().ok_or("s is empty")?;
+                Ok(())
+            }
+
+            fn size_hint(&self) -> usize {
+                self.1
+            }
+        }
+
+        let signals = Rc::new(RefCell::new(Vec::new()));
+        let sink = signals.clone();
+        let mut target = String::new();
+        let mut record = record::Builder::new()
+            .budget(100)
+            .connect(
+                Box::new(move |signal| sink.borrow_mut().push(signal)) as Box<dyn FnMut(Signal)>
+            )
+            .build();
+        record.apply(&mut target, Image('a', 40)).unwrap();
+        record.apply(&mut target, Image('b', 40)).unwrap();
+        record.set_saved(true);
+        assert_eq!(record.memory_usage(), 80);
+        record.apply(&mut target, Image('c', 50)).unwrap();
+        assert_eq!(record.len(), 2);
+        assert_eq!(record.current(), 2);
+        assert_eq!(record.memory_usage(), 90);
+        record.undo(&mut target).unwrap().unwrap();
+        assert!(record.is_saved());
+
+        record.apply(&mut target, Image('d', 200)).unwrap();
         assert_eq!(record.len(), 1);
+        assert_eq!(record.memory_usage(), 200);
+        assert!(!record.is_saved());
+        record.undo(&mut target).unwrap().unwrap();
+        assert!(!record.can_undo());
+        assert_eq!(target, "ab");
+        let evicted = signals
+            .borrow()
+            .iter()
+            .filter(|&&signal| signal == Signal::Evicted)
+            .count();
+        assert_eq!(evicted, 2);
     }
 }
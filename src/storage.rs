@@ -0,0 +1,659 @@
+//! Storage of the entries in a record.
+//!
+//! By default a record holds its entries in memory, in a `VecDeque`.
+//! A record can instead be built with another [`Storage`] by using
+//! [`Builder::build_with`](../record/struct.Builder.html#method.build_with).
+//!
+//! [`Storage`]: trait.Storage.html
+
+use crate::Entry;
+use alloc::collections::VecDeque;
+use core::convert::Infallible;
+#[cfg(feature = "file")]
+use {
+    alloc::vec::Vec,
+    core::{fmt, ops::Range},
+    serde::{de::DeserializeOwned, Serialize},
+    std::{
+        fs::File,
+        io::{self, Read, Seek, SeekFrom, Write},
+        vec,
+    },
+};
+
+/// Holds the entries of a record.
+///
+/// The entries are indexed by their position in the record, where `0` is the oldest entry.
+/// The record tells the storage when its position changes by calling [`seek`],
+/// which allows the storage to only hold the entries around the position in memory.
+///
+/// [`seek`]: trait.Storage.html#method.seek
+pub trait Storage<A> {
+    /// The error returned when an entry can not be loaded into memory.
+    ///
+    /// The record returns it as the error of the action, see [`IntoActionError`].
+    ///
+    /// [`IntoActionError`]: trait.IntoActionError.html
+    type Error;
+
+    /// Returns the number of entries.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the entry at `index` if it is held in memory.
+    ///
+    /// Entries that are not held in memory are not loaded, so the methods that only
+    /// borrow the record, like [`undo_text`], return `None` for them.
+    ///
+    /// [`undo_text`]: ../record/struct.Record.html#method.undo_text
+    fn get(&self, index: usize) -> Option<&Entry<A>>;
+
+    /// Returns the entry at `index`, loading it into memory if needed.
+    ///
+    /// # Errors
+    /// Returns an error if the entry can not be loaded.
+    fn get_mut(&mut self, index: usize) -> Result<Option<&mut Entry<A>>, Self::Error>;
+
+    /// Appends an entry.
+    fn push_back(&mut self, entry: Entry<A>);
+
+    /// Removes the newest entry.
+    fn remove_back(&mut self);
+
+    /// Removes the oldest entry.
+    fn remove_front(&mut self);
+
+    /// Removes and returns the entries from `at` and onwards.
+    ///
+    /// # Errors
+    /// Returns an error if one of the entries can not be loaded, in which case
+    /// no entries are removed.
+    fn split_off(&mut self, at: usize) -> Result<VecDeque<Entry<A>>, Self::Error>;
+
+    /// Removes all entries.
+    fn clear(&mut self);
+
+    /// Called when the position of the record changes to `current`.
+    fn seek(&mut self, current: usize) {
+        let _ = current;
+    }
+}
+
+/// Converts the error of a [`Storage`] into the error of an action.
+///
+/// [`Storage`]: trait.Storage.html
+pub trait IntoActionError<E> {
+    /// Converts the error.
+    fn into_action_error(self) -> E;
+}
+
+impl<E> IntoActionError<E> for Infallible {
+    fn into_action_error(self) -> E {
+        match self {}
+    }
+}
+
+/// Requires the `file` feature to be enabled.
+#[cfg(feature = "file")]
+impl<E: From<io::Error>> IntoActionError<E> for io::Error {
+    fn into_action_error(self) -> E {
+        E::from(self)
+    }
+}
+
+/// Holds every entry in memory.
+impl<A> Storage<A> for VecDeque<Entry<A>> {
+    type Error = Infallible;
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&Entry<A>> {
+        self.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Result<Option<&mut Entry<A>>, Infallible> {
+        Ok(self.get_mut(index))
+    }
+
+    fn push_back(&mut self, entry: Entry<A>) {
+        self.push_back(entry);
+    }
+
+    fn remove_back(&mut self) {
+        self.pop_back();
+    }
+
+    fn remove_front(&mut self) {
+        self.pop_front();
+    }
+
+    fn split_off(&mut self, at: usize) -> Result<VecDeque<Entry<A>>, Infallible> {
+        Ok(self.split_off(at))
+    }
+
+    fn clear(&mut self) {
+        self.clear();
+    }
+}
+
+/// Holds the entries around the current position in memory, and the rest in a file.
+///
+/// The entries within `window` of the current position are held in memory.
+/// When the position moves, the entries that fall out of the window are written to the file,
+/// and the entries are read back from the file when they are needed.
+/// The entries are stored using serde.
+///
+/// New entries are appended to the file, and the file is compacted when more than half
+/// of it is taken up by entries that have since been loaded or removed.
+/// It is truncated when the storage is cleared.
+///
+/// Requires the `file` feature to be enabled.
+///
+/// # Errors
+/// If an entry can not be read back from the file, the call that needed it returns the
+/// [`io::Error`] as the error of the action, so the error type of the action must implement
+/// `From<io::Error>`. If this happens while an action is applied, the action has been
+/// applied to the target but is not added to the record.
+///
+/// An entry that can not be written to the file is kept in memory.
+///
+/// # Examples
+/// ```
+/// # use undo::{record::Builder, storage::FileStorage};
+/// # use serde::{Deserialize, Serialize};
+/// # use std::io;
+/// # #[derive(Serialize, Deserialize)]
+/// # struct Add(char);
+/// # impl undo::Action for Add {
+/// #     type Target = String;
+/// #     type Output = ();
+/// #     type Error = io::Error;
+/// #     fn apply(&mut self, s: &mut String) -> undo::Result<Add> {
+/// #         s.push(self.0);
+/// #         Ok(())
+/// #     }
+/// #     fn undo(&mut self, s: &mut String) -> undo::Result<Add> {
+/// #         self.0 = s.pop().ok_or_else(|| io::Error::other("s is empty"))?;
+/// #         Ok(())
+/// #     }
+/// # }
+/// # fn main() -> io::Result<()> {
+/// let file = std::fs::File::options()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .truncate(true)
+///     .open(std::env::temp_dir().join("undo-file-storage-example"))?;
+/// let mut target = String::new();
+/// let mut record = Builder::default().build_with(FileStorage::new(file, 2)?);
+/// for c in 'a'..='z' {
+///     record.apply(&mut target, Add(c))?;
+/// }
+/// record.go_to(&mut target, 0).unwrap()?;
+/// assert_eq!(target, "");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "file")]
+pub struct FileStorage<A> {
+    entries: VecDeque<Paged<A>>,
+    /// The entries in this range are held in memory, and the rest are in the file
+    /// unless they could not be written to it.
+    hot: Range<usize>,
+    window: usize,
+    file: File,
+    /// The end of the data in the file.
+    end: u64,
+    /// The number of bytes in the file that hold entries.
+    live: u64,
+}
+
+#[cfg(feature = "file")]
+enum Paged<A> {
+    Hot(Entry<A>),
+    Cold { offset: u64, len: usize },
+}
+
+#[cfg(feature = "file")]
+impl<A> FileStorage<A> {
+    /// Returns a storage that holds the entries within `window` of the current position
+    /// in memory, and the rest in `file`.
+    ///
+    /// The window is at least `1`, so the entries that are undone and redone next can be
+    /// held in memory. The file must be opened for both reading and writing. It is truncated.
+    ///
+    /// # Errors
+    /// Returns an error if the file can not be truncated.
+    pub fn new(file: File, window: usize) -> io::Result<FileStorage<A>> {
+        file.set_len(0)?;
+        Ok(FileStorage {
+            entries: VecDeque::new(),
+            hot: 0..0,
+            window: window.max(1),
+            file,
+            end: 0,
+            live: 0,
+        })
+    }
+
+    /// Returns the number of entries on each side of the current position that are held in memory.
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Returns the number of entries that are held in memory.
+    pub fn in_memory(&self) -> usize {
+        self.hot.len()
+    }
+
+    /// Makes sure the range of entries in memory is within the entries.
+    fn clamp(&mut self) {
+        let len = self.entries.len();
+        self.hot.end = self.hot.end.min(len);
+        self.hot.start = self.hot.start.min(self.hot.end);
+    }
+
+    /// Frees the space in the file used by the removed entry.
+    fn release(&mut self, paged: Option<Paged<A>>) {
+        if let Some(Paged::Cold { len, .. }) = paged {
+            self.live -= len as u64;
+        }
+    }
+
+    fn read_bytes(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn write_bytes(&self, offset: u64, buf: &[u8]) -> io::Result<()> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(buf)
+    }
+
+    /// Moves the entries in the file towards its start to reclaim the unused space.
+    fn compact(&mut self) {
+        let mut cold: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, paged)| match *paged {
+                Paged::Cold { offset, len } => Some((offset, len, i)),
+                Paged::Hot(_) => None,
+            })
+            .collect();
+        cold.sort_unstable();
+        let mut end = 0;
+        for (offset, len, i) in cold {
+            let size = len as u64;
+            // Entries are only moved to space they do not overlap,
+            // so an entry is still intact where it was if the move fails.
+            if offset >= end + size {
+                let moved = self
+                    .read_bytes(offset, len)
+                    .and_then(|buf| self.write_bytes(end, &buf));
+                if moved.is_err() {
+                    return;
+                }
+                self.entries[i] = Paged::Cold { offset: end, len };
+                end += size;
+            } else {
+                end = offset + size;
+            }
+        }
+        // The data after the end is never read, so it is fine if the file can not be shortened.
+        let _ = self.file.set_len(end);
+        self.end = end;
+    }
+}
+
+#[cfg(feature = "file")]
+impl<A: Serialize + DeserializeOwned> FileStorage<A> {
+    fn read(&self, offset: u64, len: usize) -> io::Result<Entry<A>> {
+        let buf = self.read_bytes(offset, len)?;
+        Ok(serde_json::from_slice(&buf)?)
+    }
+
+    fn page_in(&mut self, range: Range<usize>) -> io::Result<()> {
+        // Read every entry before changing any, so the entries in memory stay in one range.
+        let mut loaded = Vec::new();
+        for i in range {
+            if let Paged::Cold { offset, len } = self.entries[i] {
+                loaded.push((i, len, self.read(offset, len)?));
+            }
+        }
+        for (i, len, entry) in loaded {
+            self.entries[i] = Paged::Hot(entry);
+            self.live -= len as u64;
+        }
+        Ok(())
+    }
+
+    fn page_out(&mut self, range: Range<usize>) {
+        for i in range {
+            let Paged::Hot(ref entry) = self.entries[i] else {
+                continue;
+            };
+            let Ok(buf) = serde_json::to_vec(entry) else {
+                continue;
+            };
+            if self.write_bytes(self.end, &buf).is_ok() {
+                self.entries[i] = Paged::Cold {
+                    offset: self.end,
+                    len: buf.len(),
+                };
+                self.end += buf.len() as u64;
+                self.live += buf.len() as u64;
+            }
+        }
+        if self.end > 2 * self.live {
+            self.compact();
+        }
+    }
+}
+
+#[cfg(feature = "file")]
+impl<A: Serialize + DeserializeOwned> Storage<A> for FileStorage<A> {
+    type Error = io::Error;
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&Entry<A>> {
+        match self.entries.get(index)? {
+            Paged::Hot(entry) => Some(entry),
+            Paged::Cold { .. } => None,
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> io::Result<Option<&mut Entry<A>>> {
+        if index >= self.entries.len() {
+            return Ok(None);
+        }
+        // Keep the entries in memory in a single range, and start a new range
+        // instead of loading every entry between the range and the index.
+        if self.hot.is_empty() || index + 1 < self.hot.start || index > self.hot.end {
+            self.page_out(self.hot.clone());
+            self.hot = index..index;
+        }
+        if index < self.hot.start {
+            self.page_in(index..self.hot.start)?;
+            self.hot.start = index;
+        } else if index >= self.hot.end {
+            self.page_in(self.hot.end..index + 1)?;
+            self.hot.end = index + 1;
+        }
+        match self.entries[index] {
+            Paged::Hot(ref mut entry) => Ok(Some(entry)),
+            Paged::Cold { .. } => unreachable!(),
+        }
+    }
+
+    fn push_back(&mut self, entry: Entry<A>) {
+        let len = self.entries.len();
+        if self.hot.end != len {
+            self.page_out(self.hot.clone());
+            self.hot = len..len;
+        }
+        self.entries.push_back(Paged::Hot(entry));
+        self.hot.end += 1;
+    }
+
+    fn remove_back(&mut self) {
+        let paged = self.entries.pop_back();
+        self.release(paged);
+        self.clamp();
+    }
+
+    fn remove_front(&mut self) {
+        let paged = self.entries.pop_front();
+        self.release(paged);
+        self.hot.start = self.hot.start.saturating_sub(1);
+        self.hot.end = self.hot.end.saturating_sub(1);
+    }
+
+    fn split_off(&mut self, at: usize) -> io::Result<VecDeque<Entry<A>>> {
+        // Read the entries before removing any, so no entries are lost if one can not be read.
+        let mut loaded = Vec::new();
+        for paged in self.entries.range(at..) {
+            if let Paged::Cold { offset, len } = *paged {
+                loaded.push(self.read(offset, len)?);
+            }
+        }
+        let mut loaded = loaded.into_iter();
+        let tail = self.entries.split_off(at);
+        self.clamp();
+        let mut entries = VecDeque::with_capacity(tail.len());
+        for paged in tail {
+            match paged {
+                Paged::Hot(entry) => entries.push_back(entry),
+                Paged::Cold { len, .. } => {
+                    self.live -= len as u64;
+                    entries.extend(loaded.next());
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.hot = 0..0;
+        // The offsets are tracked, so the old content is overwritten if this fails.
+        let _ = self.file.set_len(0);
+        self.end = 0;
+        self.live = 0;
+    }
+
+    fn seek(&mut self, current: usize) {
+        let len = self.entries.len();
+        let start = current.saturating_sub(self.window).min(len);
+        let end = current.saturating_add(self.window).min(len);
+        if self.hot.start < start {
+            self.page_out(self.hot.start..start.min(self.hot.end));
+        }
+        if self.hot.end > end {
+            self.page_out(end.max(self.hot.start)..self.hot.end);
+        }
+        let start = self.hot.start.max(start);
+        let end = self.hot.end.min(end);
+        self.hot = start.min(end)..end;
+        // Load the entries that are undone and redone next, so their text is available.
+        // If they can not be read, the error is returned when they are undone or redone.
+        let _ = self.get_mut(current.saturating_sub(1));
+        let _ = self.get_mut(current);
+    }
+}
+
+#[cfg(feature = "file")]
+impl<A> fmt::Debug for FileStorage<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FileStorage")
+            .field("len", &self.entries.len())
+            .field("hot", &self.hot)
+            .field("window", &self.window)
+            .field("file", &self.file)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "file"))]
+mod tests {
+    use crate::storage::{FileStorage, Storage};
+    use crate::*;
+    use alloc::string::String;
+    use core::fmt::{self, Display, Formatter};
+    use serde::{Deserialize, Serialize};
+    use std::{
+        fs::{self, File},
+        io,
+        path::PathBuf,
+    };
+
+    #[derive(Serialize, Deserialize)]
+    struct Add(char);
+
+    impl Action for Add {
+        type Target = String;
+        type Output = ();
+        type Error = io::Error;
+
+        fn apply(&mut self, s: &mut String) -> Result<Add> {
+            s.push(self.0);
+            Ok(())
+        }
+
+        fn undo(&mut self, s: &mut String) -> Result<Add> {
+            self.0 = s.pop().ok_or_else(|| io::Error::other("s is empty"))?;
+            Ok(())
+        }
+    }
+
+    impl Display for Add {
+        fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    fn open(name: &str) -> (PathBuf, File) {
+        let path =
+            std::env::temp_dir().join(std::format!("undo-storage-{name}-{}", std::process::id()));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn file() {
+        let (path, file) = open("file");
+        let mut target = String::new();
+        let mut record = record::Builder::default()
+            .limit(20)
+            .build_with(FileStorage::new(file, 2).unwrap());
+        for c in 'a'..='z' {
+            record.apply(&mut target, Add(c)).unwrap();
+        }
+        assert_eq!(record.len(), 20);
+        assert_eq!(record.entries.in_memory(), 2);
+
+        record.go_to(&mut target, 5).unwrap().unwrap();
+        assert_eq!(target, "abcdefghijk");
+        assert!(record.entries.in_memory() <= 4);
+        assert!(record.can_redo());
+        assert!(record.entries.get(5).is_some());
+        assert_eq!(record.undo_text().unwrap(), "k");
+        assert_eq!(record.redo_text().unwrap(), "l");
+
+        record.apply(&mut target, Add('z')).unwrap();
+        assert_eq!(record.len(), 6);
+        record.go_to(&mut target, 0).unwrap().unwrap();
+        assert_eq!(target, "abcdef");
+        record.go_to(&mut target, 6).unwrap().unwrap();
+        assert_eq!(target, "abcdefghijkz");
+
+        record.clear();
+        assert!(record.entries.is_empty());
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn compact() {
+        let (path, file) = open("compact");
+        let mut target = String::new();
+        let mut record = record::Builder::default()
+            .limit(10)
+            .build_with(FileStorage::new(file, 1).unwrap());
+        for _ in 0..100 {
+            record.apply(&mut target, Add('a')).unwrap();
+        }
+        // The file holds at most 9 entries, and at most as much unused space.
+        let entry = serde_json::to_vec(record.entries.get(9).unwrap())
+            .unwrap()
+            .len() as u64;
+        assert!(fs::metadata(&path).unwrap().len() <= 2 * 9 * entry);
+        for _ in 0..10 {
+            record.go_to(&mut target, 0).unwrap().unwrap();
+            record.go_to(&mut target, 10).unwrap().unwrap();
+        }
+        assert_eq!(target.len(), 100);
+        assert!(fs::metadata(&path).unwrap().len() <= 2 * 9 * entry);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn budget() {
+        #[derive(Serialize, Deserialize)]
+        struct Image(char, usize);
+
+        impl Action for Image {
+            type Target = String;
+            type Output = ();
+            type Error = io::Error;
+
+            fn apply(&mut self, s: &mut String) -> Result<Image> {
+                s.push(self.0);
+                Ok(())
+            }
+
+            fn undo(&mut self, s: &mut String) -> Result<Image> {
+                self.0 = s.pop().ok_or_else(|| io::Error::other("s is empty"))?;
+                Ok(())
+            }
+
+            fn size_hint(&self) -> usize {
+                self.1
+            }
+        }
+
+        let (path, file) = open("budget");
+        let mut target = String::new();
+        let mut record = record::Builder::default()
+            .budget(100)
+            .build_with(FileStorage::new(file, 1).unwrap());
+        for c in 'a'..='e' {
+            record.apply(&mut target, Image(c, 10)).unwrap();
+        }
+        assert_eq!(record.memory_usage(), 10);
+
+        // The entries in the file do not count towards the budget and are not evicted.
+        record.apply(&mut target, Image('f', 200)).unwrap();
+        assert_eq!(record.len(), 6);
+        assert_eq!(record.memory_usage(), 200);
+        record.go_to(&mut target, 0).unwrap().unwrap();
+        assert_eq!(target, "");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn unreadable() {
+        let (path, file) = open("unreadable");
+        let mut target = String::new();
+        let mut record = record::Builder::default().build_with(FileStorage::new(file, 1).unwrap());
+        for c in 'a'..='j' {
+            record.apply(&mut target, Add(c)).unwrap();
+        }
+        fs::write(&path, b"not an entry").unwrap();
+
+        record.undo(&mut target).unwrap().unwrap();
+        assert!(record.undo(&mut target).unwrap().is_err());
+        assert_eq!(target, "abcdefghi");
+        assert_eq!(record.current(), 9);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,14 +1,27 @@
 //! A timeline of actions.
 
-use crate::{Action, Entry, Merged, Result, Signal, Slot};
+use crate::layer::{Guard, Layer};
+use crate::{failed_at, Action, Entry, Error, Merged, Result, RollbackError, Signal, Slot};
 use arrayvec::ArrayVec;
 use core::fmt;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "postcard")]
+use {
+    crate::convert,
+    core::marker::PhantomData,
+    serde::{
+        de::{self, SeqAccess, Visitor},
+        Deserializer, Serializer,
+    },
+};
 #[cfg(feature = "alloc")]
 use {
-    crate::{At, Format},
-    alloc::string::{String, ToString},
+    crate::{intercept::Intercept, layer::Stack, At, Format, Group, Subscription},
+    alloc::{
+        string::{String, ToString},
+        vec::Vec,
+    },
     core::fmt::Write,
 };
 #[cfg(feature = "chrono")]
@@ -55,17 +68,20 @@ pub struct Timeline<A, F, const LIMIT: usize> {
     current: usize,
     saved: Option<usize>,
     slot: Slot<F>,
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")
+    )]
+    groups: Vec<Group<A>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    guard: Guard<A>,
 }
 
 impl<A, const LIMIT: usize> Timeline<A, fn(Signal), LIMIT> {
     /// Returns a new timeline.
     pub fn new() -> Timeline<A, fn(Signal), LIMIT> {
-        Timeline {
-            entries: ArrayVec::new(),
-            current: 0,
-            saved: Some(0),
-            slot: Slot::default(),
-        }
+        Builder::new().build()
     }
 }
 
@@ -97,6 +113,29 @@ impl<A, F, const LIMIT: usize> Timeline<A, F, LIMIT> {
         self.slot.f.take()
     }
 
+    /// Subscribes a listener that is called with every signal, in addition to the slot.
+    ///
+    /// The listener must be `Send` and `Sync` so the data structure can still be shared between threads.
+    /// The returned handle can be used to [`unsubscribe`] the listener.
+    ///
+    /// Requires the `alloc` feature to be enabled.
+    ///
+    /// [`unsubscribe`]: struct.Timeline.html#method.unsubscribe
+    #[cfg(feature = "alloc")]
+    pub fn subscribe(&mut self, f: impl FnMut(Signal) + Send + Sync + 'static) -> Subscription {
+        self.slot.subscribe(f)
+    }
+
+    /// Unsubscribes the listener.
+    ///
+    /// Returns `true` if the listener was subscribed.
+    ///
+    /// Requires the `alloc` feature to be enabled.
+    #[cfg(feature = "alloc")]
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        self.slot.unsubscribe(subscription)
+    }
+
     /// Returns `true` if the timeline can undo.
     pub fn can_undo(&self) -> bool {
         self.current() > 0
@@ -109,7 +148,7 @@ impl<A, F, const LIMIT: usize> Timeline<A, F, LIMIT> {
 
     /// Returns `true` if the target is in a saved state, `false` otherwise.
     pub fn is_saved(&self) -> bool {
-        self.saved.map_or(false, |saved| saved == self.current())
+        self.saved.is_some_and(|saved| saved == self.current())
     }
 
     /// Returns the position of the current action.
@@ -117,9 +156,28 @@ impl<A, F, const LIMIT: usize> Timeline<A, F, LIMIT> {
         self.current
     }
 
+    /// Returns `true` if a group has been begun but not yet ended.
+    ///
+    /// Requires the `alloc` feature to be enabled.
+    #[cfg(feature = "alloc")]
+    pub fn is_grouping(&self) -> bool {
+        !self.groups.is_empty()
+    }
+
+    /// Begins a group of actions.
+    ///
+    /// Requires the `alloc` feature to be enabled.
+    ///
+    /// The methods that move in the timeline end all open groups before moving.
+    /// See [`Record::begin_group`](../record/struct.Record.html#method.begin_group) for more information.
+    #[cfg(feature = "alloc")]
+    pub fn begin_group(&mut self, label: Option<String>) {
+        self.groups.push(Group::new(label));
+    }
+
     /// Returns a structure for configurable formatting of the record.
     #[cfg(feature = "alloc")]
-    pub fn display(&self) -> Display<A, F, LIMIT> {
+    pub fn display(&self) -> Display<'_, A, F, LIMIT> {
         Display::from(self)
     }
 }
@@ -131,8 +189,29 @@ impl<A: Action, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F, LIMIT> {
     /// If an error occur when executing [`apply`] the error is returned.
     ///
     /// [`apply`]: trait.Action.html#tymethod.apply
-    pub fn apply(&mut self, target: &mut A::Target, mut action: A) -> Result<A> {
-        let output = action.apply(target)?;
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "apply",
+            level = "debug",
+            skip_all,
+            fields(current = self.current)
+        )
+    )]
+    pub fn apply(&mut self, target: &mut A::Target, action: A) -> Result<A> {
+        let mut entry = Entry::from(action);
+        let output = entry.apply(target, &self.guard, self.current)?;
+        // Defer the action until the group is ended.
+        #[cfg(feature = "alloc")]
+        if let Some(group) = self.groups.last_mut() {
+            group.push(entry.action);
+            return Ok(output);
+        }
+        self.push(entry);
+        Ok(output)
+    }
+
+    fn push(&mut self, entry: Entry<A>) {
         let current = self.current();
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
@@ -143,47 +222,96 @@ impl<A: Action, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F, LIMIT> {
         self.saved = self.saved.filter(|&saved| saved <= current);
         // Try to merge actions unless the target is in a saved state.
         let merged = match self.entries.last_mut() {
-            Some(last) if !was_saved => last.action.merge(action),
-            _ => Merged::No(action),
+            Some(last) if !was_saved => last.merge(entry),
+            _ => Merged::No(entry),
         };
         match merged {
-            Merged::Yes => (),
+            Merged::Yes => {
+                self.slot.emit(Signal::Merged);
+                #[cfg(feature = "tracing")]
+                tracing::debug!(position = current - 1, "merged");
+            }
             Merged::Annul => {
                 self.entries.pop();
                 self.current -= 1;
+                self.slot.emit(Signal::Annulled);
+                #[cfg(feature = "tracing")]
+                tracing::debug!(position = self.current, "annulled");
             }
             // If actions are not merged or annulled push it onto the record.
-            Merged::No(action) => {
+            Merged::No(entry) => {
                 // If limit is reached, pop off the first action.
                 if LIMIT == self.current() {
                     self.entries.pop_at(0);
                     self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+                    self.slot.emit(Signal::Evicted);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(limit = LIMIT, "evicted");
                 } else {
                     self.current += 1;
                 }
-                self.entries.push(Entry::from(action));
+                self.entries.push(entry);
+                self.slot.emit(Signal::Applied(self.current - 1));
             }
         };
         self.slot.emit_if(could_redo, Signal::Redo(false));
         self.slot.emit_if(!could_undo, Signal::Undo(true));
         self.slot.emit_if(was_saved, Signal::Saved(false));
-        Ok(output)
+    }
+
+    /// Ends the most recently begun group.
+    ///
+    /// Requires the `alloc` feature to be enabled.
+    ///
+    /// See [`Record::end_group`](../record/struct.Record.html#method.end_group) for more information.
+    #[cfg(feature = "alloc")]
+    pub fn end_group(&mut self) {
+        let Some(group) = self.groups.pop() else {
+            return;
+        };
+        if let Some(outer) = self.groups.last_mut() {
+            outer.extend(group);
+        } else if let Some(entry) = group.into_entry() {
+            self.push(entry);
+        }
+    }
+
+    fn end_groups(&mut self) {
+        #[cfg(feature = "alloc")]
+        while self.is_grouping() {
+            self.end_group();
+        }
     }
 
     /// Calls the [`undo`] method for the active action and sets
     /// the previous one as the new active one.
     ///
+    /// Any open [group](struct.Timeline.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
     /// # Errors
     /// If an error occur when executing [`undo`] the error is returned.
     ///
     /// [`undo`]: ../trait.Action.html#tymethod.undo
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "undo",
+            level = "debug",
+            skip_all,
+            fields(current = self.current)
+        )
+    )]
     pub fn undo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.end_groups();
         self.can_undo().then(|| {
             let was_saved = self.is_saved();
             let old = self.current();
-            let output = self.entries[self.current - 1].action.undo(target)?;
+            let output =
+                self.entries[self.current - 1].undo(target, &self.guard, self.current - 1)?;
             self.current -= 1;
             let is_saved = self.is_saved();
+            self.slot.emit(Signal::Undone(self.current));
             self.slot.emit_if(old == self.len(), Signal::Redo(true));
             self.slot.emit_if(old == 1, Signal::Undo(false));
             self.slot
@@ -195,17 +323,31 @@ impl<A: Action, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F, LIMIT> {
     /// Calls the [`redo`] method for the active action and sets
     /// the next one as the new active one.
     ///
+    /// Any open [group](struct.Timeline.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
     /// # Errors
     /// If an error occur when applying [`redo`] the error is returned.
     ///
     /// [`redo`]: trait.Action.html#method.redo
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "redo",
+            level = "debug",
+            skip_all,
+            fields(current = self.current)
+        )
+    )]
     pub fn redo(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.end_groups();
         self.can_redo().then(|| {
             let was_saved = self.is_saved();
             let old = self.current();
-            let output = self.entries[self.current].action.redo(target)?;
+            let output = self.entries[self.current].redo(target, &self.guard, self.current)?;
             self.current += 1;
             let is_saved = self.is_saved();
+            self.slot.emit(Signal::Redone(old));
             self.slot
                 .emit_if(old == self.len() - 1, Signal::Redo(false));
             self.slot.emit_if(old == 0, Signal::Undo(true));
@@ -215,6 +357,46 @@ impl<A: Action, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F, LIMIT> {
         })
     }
 
+    /// Like [`undo`], but returns an error instead of `None` if there is nothing to undo.
+    ///
+    /// # Errors
+    /// Returns [`Error::NothingToUndo`] if there is nothing to undo,
+    /// or [`Error::Action`] with the index of the action if an error occur when executing [`undo`].
+    ///
+    /// [`undo`]: struct.Timeline.html#method.undo
+    /// [`Error::NothingToUndo`]: ../enum.Error.html#variant.NothingToUndo
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    pub fn try_undo(
+        &mut self,
+        target: &mut A::Target,
+    ) -> core::result::Result<A::Output, Error<A::Error>> {
+        self.end_groups();
+        let current = self.current();
+        self.undo(target)
+            .ok_or(Error::NothingToUndo)?
+            .map_err(|error| Error::Action(error, current - 1))
+    }
+
+    /// Like [`redo`], but returns an error instead of `None` if there is nothing to redo.
+    ///
+    /// # Errors
+    /// Returns [`Error::NothingToRedo`] if there is nothing to redo,
+    /// or [`Error::Action`] with the index of the action if an error occur when executing [`redo`].
+    ///
+    /// [`redo`]: struct.Timeline.html#method.redo
+    /// [`Error::NothingToRedo`]: ../enum.Error.html#variant.NothingToRedo
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    pub fn try_redo(
+        &mut self,
+        target: &mut A::Target,
+    ) -> core::result::Result<A::Output, Error<A::Error>> {
+        self.end_groups();
+        let current = self.current();
+        self.redo(target)
+            .ok_or(Error::NothingToRedo)?
+            .map_err(|error| Error::Action(error, current))
+    }
+
     /// Marks the target as currently being in a saved or unsaved state.
     pub fn set_saved(&mut self, saved: bool) {
         let was_saved = self.is_saved();
@@ -227,54 +409,103 @@ impl<A: Action, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F, LIMIT> {
         }
     }
 
+    /// Calls `f` with the timeline while holding back the signals,
+    /// and then only emits the net changes in the state.
+    ///
+    /// See [`Record::batch_signals`](../record/struct.Record.html#method.batch_signals) for more information.
+    pub fn batch_signals<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let muted = self.slot.mute(true);
+        let r = f(self);
+        self.slot.mute(muted);
+        let can_undo = self.can_undo();
+        let can_redo = self.can_redo();
+        let is_saved = self.is_saved();
+        self.slot
+            .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
+        self.slot
+            .emit_if(could_redo != can_redo, Signal::Redo(can_redo));
+        self.slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
+        r
+    }
+
     /// Removes all actions from the timeline without undoing them.
     pub fn clear(&mut self) {
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
         self.entries.clear();
+        #[cfg(feature = "alloc")]
+        self.groups.clear();
         self.saved = self.is_saved().then_some(0);
         self.current = 0;
+        self.slot.emit(Signal::Cleared);
         self.slot.emit_if(could_undo, Signal::Undo(false));
         self.slot.emit_if(could_redo, Signal::Redo(false));
     }
-}
 
-impl<A: Action<Output = ()>, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F, LIMIT> {
-    /// Revert the changes done to the target since the saved state.
-    pub fn revert(&mut self, target: &mut A::Target) -> Option<Result<A>> {
-        self.saved.and_then(|saved| self.go_to(target, saved))
+    /// Like [`revert`], but calls `f` with the output of each action that is undone or redone.
+    ///
+    /// [`revert`]: struct.Timeline.html#method.revert
+    pub fn revert_with(
+        &mut self,
+        target: &mut A::Target,
+        f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        self.end_groups();
+        self.saved
+            .and_then(|saved| self.go_to_with(target, saved, f))
     }
 
-    /// Repeatedly calls [`undo`] or [`redo`] until the action at `current` is reached.
+    /// Like [`go_to`], but calls `f` with the output of each action that is undone or redone.
     ///
-    /// # Errors
-    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
-    ///
-    /// [`undo`]: trait.Action.html#tymethod.undo
-    /// [`redo`]: trait.Action.html#method.redo
-    pub fn go_to(&mut self, target: &mut A::Target, current: usize) -> Option<Result<A>> {
+    /// [`go_to`]: struct.Timeline.html#method.go_to
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "go_to",
+            level = "debug",
+            skip_all,
+            fields(from = self.current, to = current)
+        )
+    )]
+    pub fn go_to_with(
+        &mut self,
+        target: &mut A::Target,
+        current: usize,
+        mut f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
+        self.end_groups();
         if current > self.len() {
             return None;
         }
         let could_undo = self.can_undo();
         let could_redo = self.can_redo();
         let was_saved = self.is_saved();
-        // Temporarily remove slot so they are not called each iteration.
-        let slot = self.disconnect();
+        let start = self.current();
+        // Temporarily mute the slot so it is not called each iteration.
+        let muted = self.slot.mute(true);
         // Decide if we need to undo or redo to reach current.
-        let f = if current > self.current() {
+        let step = if current > self.current() {
             Timeline::redo
         } else {
             Timeline::undo
         };
         while self.current() != current {
-            if let Err(err) = f(self, target).unwrap() {
-                self.slot.f = slot;
-                return Some(Err(err));
+            match step(self, target).unwrap() {
+                Ok(output) => f(output),
+                Err(err) => {
+                    self.slot.mute(muted);
+                    self.slot.emit_moved(start, self.current);
+                    return Some(Err(err));
+                }
             }
         }
-        // Add slot back.
-        self.slot.f = slot;
+        // Unmute the slot.
+        self.slot.mute(muted);
+        self.slot.emit_moved(start, current);
         let can_undo = self.can_undo();
         let can_redo = self.can_redo();
         let is_saved = self.is_saved();
@@ -287,14 +518,126 @@ impl<A: Action<Output = ()>, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F
         Some(Ok(()))
     }
 
-    /// Go back or forward in the record to the action that was made closest to the datetime provided.
+    /// Like [`time_travel`], but calls `f` with the output of each action that is undone or redone.
+    ///
+    /// [`time_travel`]: struct.Timeline.html#method.time_travel
     #[cfg(feature = "chrono")]
-    pub fn time_travel(&mut self, target: &mut A::Target, to: &DateTime<Utc>) -> Option<Result<A>> {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "time_travel",
+            level = "debug",
+            skip_all,
+            fields(to = %to)
+        )
+    )]
+    pub fn time_travel_with(
+        &mut self,
+        target: &mut A::Target,
+        to: &DateTime<Utc>,
+        f: impl FnMut(A::Output),
+    ) -> Option<core::result::Result<(), A::Error>> {
         let current = self
             .entries
             .binary_search_by(|e| e.timestamp.cmp(to))
             .unwrap_or_else(identity);
+        self.go_to_with(target, current, f)
+    }
+}
+
+impl<A: Action<Output = ()>, F: FnMut(Signal), const LIMIT: usize> Timeline<A, F, LIMIT> {
+    /// Revert the changes done to the target since the saved state.
+    ///
+    /// Any open [group](struct.Timeline.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    pub fn revert(&mut self, target: &mut A::Target) -> Option<Result<A>> {
+        self.revert_with(target, |()| ())
+    }
+
+    /// Repeatedly calls [`undo`] or [`redo`] until the action at `current` is reached.
+    ///
+    /// Any open [group](struct.Timeline.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned.
+    ///
+    /// [`undo`]: trait.Action.html#tymethod.undo
+    /// [`redo`]: trait.Action.html#method.redo
+    pub fn go_to(&mut self, target: &mut A::Target, current: usize) -> Option<Result<A>> {
+        self.go_to_with(target, current, |()| ())
+    }
+
+    /// Like [`go_to`], but rolls back to the position it started from if an error occurs.
+    ///
+    /// # Errors
+    /// If an error occur when executing [`undo`] or [`redo`] the error is returned
+    /// together with any error that occurred during the rollback.
+    ///
+    /// [`go_to`]: struct.Timeline.html#method.go_to
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    /// [`redo`]: ../trait.Action.html#method.redo
+    pub fn go_to_atomic(
+        &mut self,
+        target: &mut A::Target,
+        current: usize,
+    ) -> Option<core::result::Result<(), RollbackError<A::Error>>> {
+        self.end_groups();
+        if current > self.len() {
+            return None;
+        }
+        let start = self.current();
+        let could_undo = self.can_undo();
+        let could_redo = self.can_redo();
+        let was_saved = self.is_saved();
+        let muted = self.slot.mute(true);
+        let result = self.go_to(target, current)?.map_err(|error| {
+            let rollback = self.go_to(target, start).and_then(|result| result.err());
+            RollbackError { error, rollback }
+        });
+        self.slot.mute(muted);
+        self.slot.emit_moved(start, self.current);
+        let can_undo = self.can_undo();
+        let can_redo = self.can_redo();
+        let is_saved = self.is_saved();
+        self.slot
+            .emit_if(could_undo != can_undo, Signal::Undo(can_undo));
+        self.slot
+            .emit_if(could_redo != can_redo, Signal::Redo(can_redo));
+        self.slot
+            .emit_if(was_saved != is_saved, Signal::Saved(is_saved));
+        Some(result)
+    }
+
+    /// Like [`go_to`], but returns an error instead of `None` if `current` is out of range.
+    ///
+    /// # Errors
+    /// Returns [`Error::OutOfRange`] if `current` is larger than the number of actions,
+    /// or [`Error::Action`] with the index of the action if an error occur
+    /// when executing [`undo`] or [`redo`].
+    ///
+    /// [`go_to`]: struct.Timeline.html#method.go_to
+    /// [`Error::OutOfRange`]: ../enum.Error.html#variant.OutOfRange
+    /// [`Error::Action`]: ../enum.Error.html#variant.Action
+    /// [`undo`]: ../trait.Action.html#tymethod.undo
+    /// [`redo`]: ../trait.Action.html#method.redo
+    pub fn try_go_to(
+        &mut self,
+        target: &mut A::Target,
+        current: usize,
+    ) -> core::result::Result<(), Error<A::Error>> {
         self.go_to(target, current)
+            .ok_or(Error::OutOfRange(current))?
+            .map_err(|error| Error::Action(error, failed_at(self.current(), current)))
+    }
+
+    /// Go back or forward in the record to the action that was made closest to the datetime provided.
+    ///
+    /// Any open [group](struct.Timeline.html#method.begin_group) is ended first,
+    /// so it is added as an entry before moving.
+    #[cfg(feature = "chrono")]
+    pub fn time_travel(&mut self, target: &mut A::Target, to: &DateTime<Utc>) -> Option<Result<A>> {
+        self.time_travel_with(target, to, |()| ())
     }
 }
 
@@ -313,7 +656,7 @@ impl<A: ToString, F, const LIMIT: usize> Timeline<A, F, LIMIT> {
     }
 
     fn text(&self, i: usize) -> Option<String> {
-        self.entries.get(i).map(|e| e.action.to_string())
+        self.entries.get(i).map(Entry::text)
     }
 }
 
@@ -334,11 +677,60 @@ impl<A: fmt::Debug, F, const LIMIT: usize> fmt::Debug for Timeline<A, F, LIMIT>
     }
 }
 
+#[cfg(feature = "postcard")]
+impl<A: Serialize, F, const LIMIT: usize> Timeline<A, F, LIMIT> {
+    /// Encodes the timeline into `buf` and returns the part of `buf` that was used.
+    ///
+    /// The encoding is compact and does not require the `alloc` feature.
+    /// The slot, the layers and any groups that have not been ended are not encoded.
+    /// The same features must be enabled when the timeline is decoded.
+    ///
+    /// Requires the `postcard` feature to be enabled.
+    ///
+    /// # Errors
+    /// Returns an error if `buf` is too small.
+    pub fn encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+    ) -> core::result::Result<&'a mut [u8], postcard::Error> {
+        let entries = EncodeEntries(&self.entries);
+        postcard::to_slice(&(self.current, self.saved, entries), buf)
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl<'de, A: Deserialize<'de>, F, const LIMIT: usize> Timeline<A, F, LIMIT> {
+    /// Decodes a timeline that was encoded with [`encode`].
+    ///
+    /// Requires the `postcard` feature to be enabled.
+    ///
+    /// # Errors
+    /// Returns an error if the timeline has more entries than `LIMIT`, or if the bytes are invalid.
+    ///
+    /// [`encode`]: struct.Timeline.html#method.encode
+    pub fn decode(bytes: &'de [u8]) -> core::result::Result<Self, DecodeError> {
+        // The length of the entries is encoded before the entries.
+        let ((_, _, len), _) = postcard::take_from_bytes::<(usize, Option<usize>, usize)>(bytes)?;
+        if len > LIMIT {
+            return Err(DecodeError::Limit { len, limit: LIMIT });
+        }
+        let (current, saved, DecodeEntries(entries)) =
+            postcard::from_bytes::<(usize, Option<usize>, DecodeEntries<A, LIMIT>)>(bytes)?;
+        convert::check_positions(entries.len(), current, saved).map_err(DecodeError::OutOfRange)?;
+        let mut timeline = Builder::new().build();
+        timeline.entries = entries;
+        timeline.current = current;
+        timeline.saved = saved;
+        Ok(timeline)
+    }
+}
+
 /// Builder for a Timeline.
 #[derive(Debug)]
-pub struct Builder<F> {
+pub struct Builder<F, L = ()> {
     saved: bool,
     slot: Slot<F>,
+    layer: L,
 }
 
 impl<F> Builder<F> {
@@ -347,30 +739,63 @@ impl<F> Builder<F> {
         Builder {
             saved: true,
             slot: Slot::default(),
+            layer: (),
         }
     }
+}
 
+impl<F, L> Builder<F, L> {
     /// Sets if the target is initially in a saved state.
     /// By default the target is in a saved state.
-    pub fn saved(mut self, saved: bool) -> Builder<F> {
+    pub fn saved(mut self, saved: bool) -> Builder<F, L> {
         self.saved = saved;
         self
     }
 
+    /// Adds a layer that wraps the calls made to the actions.
+    ///
+    /// The first layer added is the outermost one.
+    ///
+    /// Requires the `alloc` feature to be enabled.
+    #[cfg(feature = "alloc")]
+    pub fn layer<M>(self, layer: M) -> Builder<F, Stack<M, L>> {
+        Builder {
+            saved: self.saved,
+            slot: self.slot,
+            layer: Stack::new(layer, self.layer),
+        }
+    }
+
+    /// Adds an interceptor that decides if actions are allowed to be applied, undone or redone.
+    ///
+    /// This is a shorthand for adding the interceptor as a layer.
+    ///
+    /// Requires the `alloc` feature to be enabled.
+    #[cfg(feature = "alloc")]
+    pub fn intercept<I>(self, interceptor: I) -> Builder<F, Stack<Intercept<I>, L>> {
+        self.layer(Intercept(interceptor))
+    }
+
     /// Builds the record.
-    pub fn build<A, const LIMIT: usize>(self) -> Timeline<A, F, LIMIT> {
+    pub fn build<A, const LIMIT: usize>(self) -> Timeline<A, F, LIMIT>
+    where
+        L: Layer<A> + Send + Sync + 'static,
+    {
         Timeline {
             entries: ArrayVec::new(),
             current: 0,
             saved: self.saved.then_some(0),
             slot: self.slot,
+            #[cfg(feature = "alloc")]
+            groups: Vec::new(),
+            guard: Guard::new(self.layer),
         }
     }
 }
 
-impl<F: FnMut(Signal)> Builder<F> {
+impl<F: FnMut(Signal), L> Builder<F, L> {
     /// Connects the slot.
-    pub fn connect(mut self, f: F) -> Builder<F> {
+    pub fn connect(mut self, f: F) -> Builder<F, L> {
         self.slot = Slot::from(f);
         self
     }
@@ -478,11 +903,161 @@ impl<A: fmt::Display, F, const LIMIT: usize> fmt::Display for Display<'_, A, F,
     }
 }
 
+/// The error returned when a timeline can not be decoded.
+///
+/// Requires the `postcard` feature to be enabled.
+#[cfg(feature = "postcard")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The encoded timeline has `len` entries, which is more than the `limit` of the timeline.
+    Limit {
+        /// The number of encoded entries.
+        len: usize,
+        /// The limit of the timeline.
+        limit: usize,
+    },
+    /// The encoded position is larger than the number of entries.
+    OutOfRange(usize),
+    /// The bytes are not a valid encoding.
+    Postcard(postcard::Error),
+}
+
+#[cfg(feature = "postcard")]
+impl From<postcard::Error> for DecodeError {
+    fn from(error: postcard::Error) -> Self {
+        DecodeError::Postcard(error)
+    }
+}
+
+#[cfg(feature = "postcard")]
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Limit { len, limit } => {
+                write!(f, "timeline has {len} entries, but the limit is {limit}")
+            }
+            DecodeError::OutOfRange(position) => write!(f, "position {position} is out of range"),
+            DecodeError::Postcard(error) => write!(f, "timeline encoding is invalid: {error}"),
+        }
+    }
+}
+
+#[cfg(all(feature = "postcard", feature = "std"))]
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Postcard(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// An entry as it is encoded.
+///
+/// Unlike `Entry` every field is always encoded, which is required by postcard.
+#[cfg(feature = "postcard")]
+#[derive(Serialize, Deserialize)]
+struct Encoded<A, G, L> {
+    action: A,
+    /// The unit type is used without `alloc`, which takes up no space.
+    group: G,
+    label: L,
+    #[cfg(feature = "chrono")]
+    timestamp: Option<i64>,
+    #[cfg(feature = "chrono")]
+    modified: Option<i64>,
+}
+
+#[cfg(all(feature = "postcard", feature = "alloc"))]
+type EncodedEntry<'a, A> = Encoded<&'a A, &'a [A], Option<&'a str>>;
+#[cfg(all(feature = "postcard", not(feature = "alloc")))]
+type EncodedEntry<'a, A> = Encoded<&'a A, (), ()>;
+#[cfg(all(feature = "postcard", feature = "alloc"))]
+type DecodedEntry<A> = Encoded<A, Vec<A>, Option<String>>;
+#[cfg(all(feature = "postcard", not(feature = "alloc")))]
+type DecodedEntry<A> = Encoded<A, (), ()>;
+
+#[cfg(feature = "postcard")]
+struct EncodeEntries<'a, A, const LIMIT: usize>(&'a ArrayVec<Entry<A>, LIMIT>);
+
+#[cfg(feature = "postcard")]
+impl<A: Serialize, const LIMIT: usize> Serialize for EncodeEntries<'_, A, LIMIT> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|entry| {
+            #[cfg(feature = "chrono")]
+            let (timestamp, modified) = entry.timestamps();
+            EncodedEntry {
+                action: &entry.action,
+                #[cfg(feature = "alloc")]
+                group: &entry.group,
+                #[cfg(feature = "alloc")]
+                label: entry.label.as_deref(),
+                #[cfg(not(feature = "alloc"))]
+                group: (),
+                #[cfg(not(feature = "alloc"))]
+                label: (),
+                #[cfg(feature = "chrono")]
+                timestamp,
+                #[cfg(feature = "chrono")]
+                modified,
+            }
+        }))
+    }
+}
+
+/// Decodes the entries directly into the array so they are only stored once.
+#[cfg(feature = "postcard")]
+struct DecodeEntries<A, const LIMIT: usize>(ArrayVec<Entry<A>, LIMIT>);
+
+#[cfg(feature = "postcard")]
+impl<'de, A: Deserialize<'de>, const LIMIT: usize> Deserialize<'de> for DecodeEntries<A, LIMIT> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        struct EntriesVisitor<A, const LIMIT: usize>(PhantomData<A>);
+
+        impl<'de, A: Deserialize<'de>, const LIMIT: usize> Visitor<'de> for EntriesVisitor<A, LIMIT> {
+            type Value = DecodeEntries<A, LIMIT>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "at most {LIMIT} entries")
+            }
+
+            fn visit_seq<S: SeqAccess<'de>>(
+                self,
+                mut seq: S,
+            ) -> core::result::Result<Self::Value, S::Error> {
+                let mut entries = ArrayVec::new();
+                while let Some(entry) = seq.next_element::<DecodedEntry<A>>()? {
+                    #[cfg(feature = "chrono")]
+                    let (timestamp, modified) = (entry.timestamp, entry.modified);
+                    #[cfg(not(feature = "chrono"))]
+                    let (timestamp, modified) = (None, None);
+                    let entry = convert::entry(
+                        entry.action,
+                        #[cfg(feature = "alloc")]
+                        entry.group,
+                        #[cfg(feature = "alloc")]
+                        entry.label,
+                        timestamp,
+                        modified,
+                    );
+                    entries
+                        .try_push(entry)
+                        .map_err(|_| de::Error::invalid_length(LIMIT + 1, &self))?;
+                }
+                Ok(DecodeEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_seq(EntriesVisitor(PhantomData))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::*;
     use arrayvec::ArrayString;
 
+    #[cfg_attr(feature = "postcard", derive(serde::Serialize, serde::Deserialize))]
     struct Add(char);
 
     impl Action for Add {
@@ -511,4 +1086,72 @@ mod tests {
         assert_eq!(target.len(), 64);
         assert_eq!(timeline.len(), 32);
     }
+
+    #[test]
+    fn try_methods() {
+        let mut target = ArrayString::new();
+        let mut timeline = Timeline::<_, _, 32>::new();
+        assert_eq!(timeline.try_undo(&mut target), Err(Error::NothingToUndo));
+        timeline.apply(&mut target, Add('a')).unwrap();
+        timeline.apply(&mut target, Add('b')).unwrap();
+        target.clear();
+        assert_eq!(
+            timeline.try_undo(&mut target),
+            Err(Error::Action("s is empty", 1))
+        );
+        assert_eq!(
+            timeline.try_go_to(&mut target, 0),
+            Err(Error::Action("s is empty", 1))
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn intercept() {
+        struct NoRedo;
+
+        impl Interceptor<Add> for NoRedo {
+            fn redo(&self, _: &Add, _: usize) -> Result<Add> {
+                Err("redo is not allowed")
+            }
+        }
+
+        let mut target = ArrayString::new();
+        let mut timeline = timeline::Builder::default()
+            .intercept(NoRedo)
+            .build::<_, 32>();
+        timeline.apply(&mut target, Add('a')).unwrap();
+        timeline.undo(&mut target).unwrap().unwrap();
+        assert_eq!(timeline.redo(&mut target), Some(Err("redo is not allowed")));
+        assert_eq!(target.as_str(), "");
+        assert_eq!(timeline.current(), 0);
+    }
+
+    #[cfg(feature = "postcard")]
+    #[test]
+    fn encode() {
+        let mut target = ArrayString::new();
+        let mut timeline = Timeline::<_, _, 8>::new();
+        timeline.apply(&mut target, Add('a')).unwrap();
+        timeline.apply(&mut target, Add('b')).unwrap();
+        timeline.apply(&mut target, Add('c')).unwrap();
+        timeline.set_saved(true);
+        timeline.undo(&mut target).unwrap().unwrap();
+
+        let mut buf = [0; 128];
+        let bytes = timeline.encode(&mut buf).unwrap();
+        let mut decoded = Timeline::<Add, fn(Signal), 8>::decode(bytes).unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded.current(), 2);
+        assert!(!decoded.is_saved());
+        decoded.redo(&mut target).unwrap().unwrap();
+        assert_eq!(target.as_str(), "abc");
+        assert!(decoded.is_saved());
+
+        assert!(timeline.encode(&mut [0; 4]).is_err());
+        assert!(matches!(
+            Timeline::<Add, fn(Signal), 2>::decode(bytes),
+            Err(timeline::DecodeError::Limit { len: 3, limit: 2 })
+        ));
+    }
 }